@@ -1,25 +1,159 @@
 use serde::Deserialize;
 use std::path::Path;
 
+use crate::onchain::split::SplitRule;
+
 #[allow(unused)]
 const CONFIG_FILE_PATH: &str = "data/config.toml";
 
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmissionMode {
+    #[default]
+    Jito,
+    Rpc,
+    Auto,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SolCollectMode {
+    Reserve,
+    #[default]
+    Drain,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountOrder {
+    #[default]
+    Random,
+    Db,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountState {
+    #[default]
+    Any,
+    Unclaimed,
+    Claimed,
+    NotCollected,
+    ClaimedNotCollected,
+    Flagged,
+}
+
 #[derive(Deserialize, Clone)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct Config {
     pub solana_rpc_url: String,
     pub me_proxy_url: String,
+    #[serde(default = "default_mint")]
+    pub mint: String,
     pub parallelism: usize,
+    #[serde(default)]
+    pub account_order: AccountOrder,
+    #[serde(default)]
+    pub account_pubkeys: Vec<String>,
+    #[serde(default)]
+    pub account_labels: Vec<String>,
+    #[serde(default)]
+    pub account_tags: Vec<String>,
+    #[serde(default)]
+    pub account_range: Vec<usize>,
+    #[serde(default)]
+    pub account_state: AccountState,
     pub collect_me: bool,
     pub collect_sol: bool,
+    #[serde(default)]
+    pub sol_collect_mode: SolCollectMode,
+    #[serde(default)]
+    pub sol_reserve: f64,
+    #[serde(default)]
+    pub batch_collect: bool,
+    #[serde(default)]
+    pub collect_token_mints: Vec<String>,
+    #[serde(default)]
+    pub collect_all_tokens: bool,
+    #[serde(default)]
+    pub close_collected_token_accounts: bool,
+    #[serde(default)]
+    pub sweep_allow_mints: Vec<String>,
+    #[serde(default)]
+    pub sweep_deny_mints: Vec<String>,
     pub withdraw_to_cex: bool,
+    #[serde(default)]
+    pub close_ata_after_claim: bool,
+    #[serde(default)]
+    pub token_destination: String,
+    #[serde(default)]
+    pub sol_destination: String,
+    #[serde(default)]
+    pub rent_refund_address: String,
+    #[serde(default)]
+    pub split_rules: Vec<SplitRule>,
     pub jito_tip_amount: f64,
+    #[serde(default)]
+    pub jito_tip_percentile: Option<f64>,
+    #[serde(default)]
+    pub jito_tip_range: Option<[f64; 2]>,
+    #[serde(default = "default_jito_tip_escalation")]
+    pub jito_tip_escalation: f64,
+    #[serde(default = "default_claim_max_attempts")]
+    pub claim_max_attempts: u32,
+    #[serde(default)]
+    pub jito_uuid: String,
+    #[serde(default)]
+    pub jito_requests_per_second: f64,
+    #[serde(default)]
+    pub jito_simulation_rpc_url: String,
+    #[serde(default)]
+    pub pack_bundles: bool,
+    #[serde(default)]
+    pub claim_submission_mode: SubmissionMode,
+    #[serde(default = "default_jito_failures_before_rpc")]
+    pub jito_failures_before_rpc: u32,
     pub cu_price: u64,
+    #[serde(default)]
+    pub dynamic_cu_price: bool,
+    #[serde(default = "default_cu_price_percentile")]
+    pub cu_price_percentile: f64,
+    #[serde(default = "default_cu_price_cap")]
+    pub cu_price_cap: u64,
+    #[serde(default)]
+    pub priority_fee_accounts: Vec<String>,
     pub claim_sleep_range: [u64; 2],
     pub use_external_fee_pay: bool,
     pub external_fee_payer_secret: String,
 }
 
+// Keys added after the first release default to the behavior from before they existed, so
+// older config files keep working.
+
+fn default_mint() -> String {
+    "MEFNBXixkEbait3xn9bkm8WsJzXtVsaJEn4c8Sam21u".to_string()
+}
+
+fn default_jito_tip_escalation() -> f64 {
+    1.0
+}
+
+fn default_claim_max_attempts() -> u32 {
+    1
+}
+
+fn default_jito_failures_before_rpc() -> u32 {
+    3
+}
+
+fn default_cu_price_percentile() -> f64 {
+    75.0
+}
+
+fn default_cu_price_cap() -> u64 {
+    2_000_000
+}
+
 impl Config {
     async fn read_from_file(path: impl AsRef<Path>) -> eyre::Result<Self> {
         let cfg_str = tokio::fs::read_to_string(path).await?;
@@ -32,3 +166,29 @@ impl Config {
             .expect("Default config to be valid")
     }
 }
+
+#[cfg(test)]
+impl Config {
+    /// Config with only the keys that have no default, followed by `extra` TOML lines
+    pub fn for_tests(extra: &str) -> Self {
+        let base = r#"
+            SOLANA_RPC_URL = ""
+            ME_PROXY_URL = ""
+            PARALLELISM = 1
+            WITHDRAW_TO_CEX = false
+            COLLECT_ME = false
+            COLLECT_SOL = false
+            JITO_TIP_AMOUNT = 0.0003
+            CU_PRICE = 800000
+            CLAIM_SLEEP_RANGE = [4, 10]
+            USE_EXTERNAL_FEE_PAY = false
+            EXTERNAL_FEE_PAYER_SECRET = ""
+        "#;
+
+        // Keys in `extra` override the base ones
+        let mut table: toml::Table = toml::from_str(base).expect("Test config to be valid");
+        table.extend(toml::from_str::<toml::Table>(extra).expect("Test config to be valid"));
+
+        table.try_into().expect("Test config to be valid")
+    }
+}
@@ -4,23 +4,77 @@
 use reqwest::Proxy;
 use serde::{Deserialize, Serialize};
 use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use tracing::{field::Empty, Span};
 
-use crate::onchain::crypto::{get_address, get_wallet};
+use crate::{
+    config::Config,
+    onchain::{
+        crypto::{get_address, get_wallet},
+        split::SplitRule,
+    },
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimAttemptStatus {
+    Landed,
+    NotLanded,
+    Dropped,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClaimAttempt {
+    pub bundle_id: String,
+    pub jito_tip: u64,
+    pub status: ClaimAttemptStatus,
+}
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Account {
     secret: String,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
     cex_address: Option<String>,
+    #[serde(default)]
+    cex_memo: Option<String>,
+    #[serde(default)]
+    sol_address: Option<String>,
+    #[serde(default)]
+    rent_address: Option<String>,
     proxy: String,
     closed_ata: bool,
     collected_sol: bool,
+    #[serde(default)]
+    jito_tip: Option<u64>,
+    #[serde(default)]
+    flag: Option<String>,
+    #[serde(default)]
+    claim_attempts: Vec<ClaimAttempt>,
+    #[serde(default)]
+    verified_me_balance: Option<u64>,
+    #[serde(default)]
+    verified_cex_balance: Option<u64>,
+    #[serde(default)]
+    split_rules: Option<Vec<SplitRule>>,
 }
 
 impl Account {
-    pub fn new(secret: &str, cex_address: Option<String>, proxy: &str) -> Self {
+    pub fn new(
+        secret: &str,
+        cex_address: Option<String>,
+        cex_memo: Option<String>,
+        sol_address: Option<String>,
+        rent_address: Option<String>,
+        proxy: &str,
+    ) -> Self {
         Self {
             secret: secret.to_string(),
             cex_address,
+            cex_memo,
+            sol_address,
+            rent_address,
             proxy: proxy.to_string(),
             ..Default::default()
         }
@@ -30,6 +84,10 @@ impl Account {
         Proxy::all(&self.proxy).expect("Proxy to be valid")
     }
 
+    pub fn get_proxy_url(&self) -> &str {
+        &self.proxy
+    }
+
     pub fn keypair(&self) -> eyre::Result<Keypair> {
         get_wallet(&self.secret)
     }
@@ -39,10 +97,83 @@ impl Account {
         get_address(&wallet)
     }
 
+    pub fn get_label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn set_label(&mut self, label: Option<String>) {
+        self.label = label
+    }
+
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags
+    }
+
+    /// `label (pubkey)`, or just the pubkey for unlabeled accounts
+    pub fn name(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{} ({})", label, self.get_pubkey()),
+            None => self.get_pubkey().to_string(),
+        }
+    }
+
+    /// Span that adds the account's label and tags to every log line emitted inside it
+    pub fn span(&self) -> Span {
+        accounts_span([self])
+    }
+
     pub fn get_cex_address(&self) -> Option<&str> {
         self.cex_address.as_deref()
     }
 
+    pub fn get_cex_memo(&self) -> Option<&str> {
+        self.cex_memo.as_deref()
+    }
+
+    /// Where tokens go: the account's own address along with its memo, else `TOKEN_DESTINATION`.
+    pub fn get_token_destination<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> Option<(&'a str, Option<&'a str>)> {
+        match &self.cex_address {
+            Some(address) => Some((address, self.cex_memo.as_deref())),
+            None => non_empty(&config.token_destination).map(|address| (address, None)),
+        }
+    }
+
+    /// Where SOL goes: the account's own SOL address, else `SOL_DESTINATION`, else wherever
+    /// its tokens go.
+    pub fn get_sol_destination<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> Option<(&'a str, Option<&'a str>)> {
+        match self
+            .sol_address
+            .as_deref()
+            .or(non_empty(&config.sol_destination))
+        {
+            Some(address) => Some((address, None)),
+            None => self.get_token_destination(config),
+        }
+    }
+
+    /// Where the rent of closed token accounts goes: the account's own rent address, else
+    /// `RENT_REFUND_ADDRESS`. `None` means the fee payer.
+    pub fn get_rent_destination<'a>(&'a self, config: &'a Config) -> Option<&'a str> {
+        self.rent_address
+            .as_deref()
+            .or(non_empty(&config.rent_refund_address))
+    }
+
+    /// The account's own split rules, falling back to `default` (usually the global ones).
+    pub fn get_split_rules<'a>(&'a self, default: &'a [SplitRule]) -> &'a [SplitRule] {
+        self.split_rules.as_deref().unwrap_or(default)
+    }
+
     pub fn get_closed_ata(&self) -> bool {
         self.closed_ata
     }
@@ -58,4 +189,90 @@ impl Account {
     pub fn set_collected_sol(&mut self, collected_sol: bool) {
         self.collected_sol = collected_sol
     }
+
+    pub fn get_jito_tip(&self) -> Option<u64> {
+        self.jito_tip
+    }
+
+    pub fn set_jito_tip(&mut self, jito_tip: u64) {
+        self.jito_tip = Some(jito_tip)
+    }
+
+    pub fn get_flag(&self) -> Option<&str> {
+        self.flag.as_deref()
+    }
+
+    pub fn set_flag(&mut self, flag: String) {
+        self.flag = Some(flag)
+    }
+
+    pub fn clear_flag(&mut self) {
+        self.flag = None
+    }
+
+    pub fn get_claim_attempts(&self) -> &[ClaimAttempt] {
+        &self.claim_attempts
+    }
+
+    pub fn push_claim_attempt(&mut self, attempt: ClaimAttempt) {
+        self.claim_attempts.push(attempt)
+    }
+
+    pub fn get_verified_me_balance(&self) -> Option<u64> {
+        self.verified_me_balance
+    }
+
+    pub fn get_verified_cex_balance(&self) -> Option<u64> {
+        self.verified_cex_balance
+    }
+
+    pub fn set_verified_balances(&mut self, me_balance: u64, cex_balance: Option<u64>) {
+        self.verified_me_balance = Some(me_balance);
+        self.verified_cex_balance = cex_balance;
+    }
+
+    /// A claim of this account landed
+    pub fn is_claimed(&self) -> bool {
+        self.verified_me_balance.is_some()
+            || self
+                .claim_attempts
+                .iter()
+                .any(|attempt| attempt.status == ClaimAttemptStatus::Landed)
+    }
+
+    pub fn is_collected(&self) -> bool {
+        self.closed_ata && self.collected_sol
+    }
+}
+
+/// Span over several accounts, e.g. the wallets sharing a bundle or a batched transaction
+pub fn accounts_span<'a>(accounts: impl IntoIterator<Item = &'a Account>) -> Span {
+    let mut labels = vec![];
+    let mut tags: Vec<&str> = vec![];
+
+    for account in accounts {
+        labels.extend(account.get_label());
+
+        for tag in account.get_tags() {
+            if !tags.contains(&tag.as_str()) {
+                tags.push(tag);
+            }
+        }
+    }
+
+    let span = tracing::info_span!("account", label = Empty, tags = Empty);
+
+    if !labels.is_empty() {
+        span.record("label", labels.join(", ").as_str());
+    }
+
+    if !tags.is_empty() {
+        span.record("tags", tags.join(", ").as_str());
+    }
+
+    span
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value).filter(|value| !value.is_empty())
 }
@@ -1,6 +1,5 @@
 use std::fs::File;
 
-use rand::{seq::IteratorRandom, thread_rng};
 use serde::{Deserialize, Serialize};
 use tokio::io::AsyncWriteExt;
 
@@ -9,6 +8,7 @@ use crate::{config::Config, utils::files::read_file_lines};
 use super::{
     account::Account,
     constants::{CEX_ADDRESSES_FILE_PATH, DB_FILE_PATH, PROXIES_FILE_PATH, SECRETS_FILE_PATH},
+    selector::AccountSelector,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -66,14 +66,32 @@ impl Database {
 
         let mut data = Vec::with_capacity(secrets.len());
 
-        for (i, secret) in secrets.into_iter().enumerate() {
-            let cex_address = cex_addresses
+        for (i, line) in secrets.into_iter().enumerate() {
+            let (secret, label, tags) = parse_secret_line(&line);
+
+            let [cex_address, cex_memo, sol_address, rent_address] = match cex_addresses
                 .as_ref()
-                .and_then(|addresses| addresses.get(i).cloned());
+                .and_then(|addresses| addresses.get(i))
+            {
+                Some(line) => parse_destinations(line).map_err(|e| {
+                    eyre::eyre!("{} line {}: {}", CEX_ADDRESSES_FILE_PATH, i + 1, e)
+                })?,
+                None => Default::default(),
+            };
 
             let proxy = &proxies[i];
 
-            let account = Account::new(&secret, cex_address, proxy);
+            let mut account = Account::new(
+                secret,
+                cex_address,
+                cex_memo,
+                sol_address,
+                rent_address,
+                proxy,
+            );
+            account.set_label(label);
+            account.set_tags(tags);
+
             data.push(account);
         }
 
@@ -83,16 +101,16 @@ impl Database {
         Ok(Self(data))
     }
 
-    pub fn get_random_account_with_filter<F>(&mut self, filter: F) -> Option<&mut Account>
+    /// Indices of the accounts picked by the configured selector that also pass `filter`
+    pub fn select<F>(&self, config: &Config, filter: F) -> eyre::Result<Vec<usize>>
     where
         F: Fn(&Account) -> bool,
     {
-        let mut rng = thread_rng();
+        let indices = AccountSelector::from_config(config)?.select(&self.0, filter);
+
+        tracing::info!("Selected {}/{} accounts", indices.len(), self.0.len());
 
-        self.0
-            .iter_mut()
-            .filter(|account| filter(account))
-            .choose(&mut rng)
+        Ok(indices)
     }
 
     pub async fn update(&self) {
@@ -105,3 +123,110 @@ impl Database {
             .expect("Failed to update database");
     }
 }
+
+/// `secret[,label[,tag1;tag2]]`, the label and tags are optional
+fn parse_secret_line(line: &str) -> (&str, Option<String>, Vec<String>) {
+    let mut fields = line.splitn(3, ',').map(str::trim);
+
+    let secret = fields.next().unwrap_or_default();
+    let label = fields
+        .next()
+        .filter(|label| !label.is_empty())
+        .map(str::to_string);
+    let tags = fields
+        .next()
+        .map(|tags| {
+            tags.split(';')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    (secret, label, tags)
+}
+
+/// `token_address[,memo[,sol_address[,rent_address]]]`, any of them may be left empty. Memos
+/// can't contain commas, so lines with more fields are rejected rather than misread.
+fn parse_destinations(line: &str) -> eyre::Result<[Option<String>; 4]> {
+    let fields: Vec<&str> = line.split(',').collect();
+
+    if fields.len() > 4 {
+        eyre::bail!(
+            "Expected at most 4 comma-separated fields, got {} (memos can't contain commas)",
+            fields.len()
+        );
+    }
+
+    Ok(std::array::from_fn(|i| {
+        fields
+            .get(i)
+            .map(|field| field.trim())
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(values: [&str; 4]) -> [Option<String>; 4] {
+        values.map(|value| Some(value.to_string()).filter(|value| !value.is_empty()))
+    }
+
+    #[test]
+    fn destinations_parse() {
+        let cases = [
+            ("token", some(["token", "", "", ""])),
+            ("token,memo", some(["token", "memo", "", ""])),
+            (
+                " token , memo , sol , rent ",
+                some(["token", "memo", "sol", "rent"]),
+            ),
+            ("token,,sol", some(["token", "", "sol", ""])),
+            (",,,rent", some(["", "", "", "rent"])),
+            ("", some(["", "", "", ""])),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(parse_destinations(line).unwrap(), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn destinations_with_extra_fields_are_rejected() {
+        assert!(parse_destinations("token,memo, with comma,sol,rent").is_err());
+        assert!(parse_destinations("token,,,,").is_err());
+    }
+
+    #[test]
+    fn secret_lines_parse() {
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+
+        let cases = [
+            ("secret", ("secret", None, tags(&[]))),
+            ("secret,", ("secret", None, tags(&[]))),
+            (" secret , farm #1 ", ("secret", Some("farm #1"), tags(&[]))),
+            ("secret,,a;b", ("secret", None, tags(&["a", "b"]))),
+            (
+                "secret,label, a ; ;b ",
+                ("secret", Some("label"), tags(&["a", "b"])),
+            ),
+            (
+                "secret,label,a,b",
+                ("secret", Some("label"), tags(&["a,b"])),
+            ),
+        ];
+
+        for (line, (secret, label, tags)) in cases {
+            assert_eq!(
+                parse_secret_line(line),
+                (secret, label.map(str::to_string), tags),
+                "{}",
+                line
+            );
+        }
+    }
+}
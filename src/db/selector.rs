@@ -0,0 +1,207 @@
+use std::{collections::HashSet, ops::RangeInclusive, str::FromStr};
+
+use rand::{seq::SliceRandom, thread_rng};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::config::{AccountOrder, AccountState, Config};
+
+use super::account::Account;
+
+/// Picks the db accounts a module works on from `ACCOUNT_ORDER`, `ACCOUNT_PUBKEYS`,
+/// `ACCOUNT_LABELS`, `ACCOUNT_TAGS`, `ACCOUNT_RANGE` and `ACCOUNT_STATE`
+#[derive(Debug, Clone)]
+pub struct AccountSelector {
+    order: AccountOrder,
+    pubkeys: HashSet<Pubkey>,
+    labels: HashSet<String>,
+    tags: HashSet<String>,
+    range: Option<RangeInclusive<usize>>,
+    state: AccountState,
+}
+
+impl AccountSelector {
+    pub fn from_config(config: &Config) -> eyre::Result<Self> {
+        let pubkeys = config
+            .account_pubkeys
+            .iter()
+            .map(|address| {
+                Pubkey::from_str(address)
+                    .map_err(|e| eyre::eyre!("Invalid ACCOUNT_PUBKEYS entry `{}`: {}", address, e))
+            })
+            .collect::<eyre::Result<HashSet<_>>>()?;
+
+        let range = match config.account_range.as_slice() {
+            [] => None,
+            [start, end] if start <= end => Some(*start..=*end),
+            range => eyre::bail!(
+                "ACCOUNT_RANGE must be empty or [start, end] with start <= end, got {:?}",
+                range
+            ),
+        };
+
+        Ok(Self {
+            order: config.account_order,
+            pubkeys,
+            labels: config.account_labels.iter().cloned().collect(),
+            tags: config.account_tags.iter().cloned().collect(),
+            range,
+            state: config.account_state,
+        })
+    }
+
+    /// Db positions of the selected accounts that also pass `filter`, in `ACCOUNT_ORDER`
+    pub fn select<F>(&self, accounts: &[Account], filter: F) -> Vec<usize>
+    where
+        F: Fn(&Account) -> bool,
+    {
+        let mut indices: Vec<usize> = accounts
+            .iter()
+            .enumerate()
+            .filter(|(index, account)| self.matches(*index, account) && filter(account))
+            .map(|(index, _)| index)
+            .collect();
+
+        if self.order == AccountOrder::Random {
+            indices.shuffle(&mut thread_rng());
+        }
+
+        indices
+    }
+
+    fn matches(&self, index: usize, account: &Account) -> bool {
+        if let Some(range) = &self.range {
+            if !range.contains(&index) {
+                return false;
+            }
+        }
+
+        if !self.pubkeys.is_empty() && !self.pubkeys.contains(&account.get_pubkey()) {
+            return false;
+        }
+
+        if !self.labels.is_empty()
+            && !account
+                .get_label()
+                .is_some_and(|label| self.labels.contains(label))
+        {
+            return false;
+        }
+
+        if !self.tags.is_empty() && !account.get_tags().iter().any(|tag| self.tags.contains(tag)) {
+            return false;
+        }
+
+        match self.state {
+            AccountState::Any => true,
+            AccountState::Unclaimed => !account.is_claimed(),
+            AccountState::Claimed => account.is_claimed(),
+            AccountState::NotCollected => !account.is_collected(),
+            AccountState::ClaimedNotCollected => account.is_claimed() && !account.is_collected(),
+            AccountState::Flagged => account.get_flag().is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Keypair;
+
+    use super::*;
+
+    /// Accounts 0..=4: 0 unclaimed, 1 claimed, 2 claimed and collected, 3 flagged and
+    /// 4 untagged and unlabeled
+    fn accounts() -> Vec<Account> {
+        (0..5)
+            .map(|index| {
+                let secret = Keypair::new().to_base58_string();
+                let mut account = Account::new(&secret, None, None, None, None, "");
+
+                if index < 4 {
+                    account.set_label(Some(format!("farm #{}", index)));
+                    account.set_tags(vec![format!("tag-{}", index % 2), "all".to_string()]);
+                }
+
+                match index {
+                    1 => account.set_verified_balances(1, None),
+                    2 => {
+                        account.set_verified_balances(1, None);
+                        account.set_closed_ata(true);
+                        account.set_collected_sol(true);
+                    }
+                    3 => account.set_flag("failed".to_string()),
+                    _ => {}
+                }
+
+                account
+            })
+            .collect()
+    }
+
+    fn selected(accounts: &[Account], extra: &str) -> Vec<usize> {
+        let config = Config::for_tests(&format!("ACCOUNT_ORDER = \"db\"\n{}", extra));
+
+        AccountSelector::from_config(&config)
+            .unwrap()
+            .select(accounts, |_| true)
+    }
+
+    #[test]
+    fn filters_match() {
+        let accounts = accounts();
+        let pubkey = accounts[3].get_pubkey();
+
+        let cases: &[(&str, &[usize])] = &[
+            ("", &[0, 1, 2, 3, 4]),
+            ("ACCOUNT_RANGE = [1, 3]", &[1, 2, 3]),
+            ("ACCOUNT_RANGE = [2, 2]", &[2]),
+            ("ACCOUNT_RANGE = [4, 10]", &[4]),
+            ("ACCOUNT_TAGS = [\"tag-1\"]", &[1, 3]),
+            ("ACCOUNT_TAGS = [\"tag-0\", \"tag-1\"]", &[0, 1, 2, 3]),
+            ("ACCOUNT_TAGS = [\"missing\"]", &[]),
+            ("ACCOUNT_LABELS = [\"farm #2\", \"farm #9\"]", &[2]),
+            ("ACCOUNT_STATE = \"any\"", &[0, 1, 2, 3, 4]),
+            ("ACCOUNT_STATE = \"unclaimed\"", &[0, 3, 4]),
+            ("ACCOUNT_STATE = \"claimed\"", &[1, 2]),
+            ("ACCOUNT_STATE = \"not_collected\"", &[0, 1, 3, 4]),
+            ("ACCOUNT_STATE = \"claimed_not_collected\"", &[1]),
+            ("ACCOUNT_STATE = \"flagged\"", &[3]),
+            (
+                "ACCOUNT_TAGS = [\"all\"]\nACCOUNT_RANGE = [2, 4]\nACCOUNT_STATE = \"unclaimed\"",
+                &[3],
+            ),
+        ];
+
+        for (extra, expected) in cases {
+            assert_eq!(selected(&accounts, extra), *expected, "{}", extra);
+        }
+
+        let extra = format!("ACCOUNT_PUBKEYS = [\"{}\"]", pubkey);
+        assert_eq!(selected(&accounts, &extra), [3]);
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        for extra in [
+            "ACCOUNT_RANGE = [3, 1]",
+            "ACCOUNT_RANGE = [1]",
+            "ACCOUNT_RANGE = [1, 2, 3]",
+            "ACCOUNT_PUBKEYS = [\"not a pubkey\"]",
+        ] {
+            let config = Config::for_tests(extra);
+            assert!(AccountSelector::from_config(&config).is_err(), "{}", extra);
+        }
+    }
+
+    #[test]
+    fn random_order_keeps_the_selection() {
+        let accounts = accounts();
+        let config = Config::for_tests("ACCOUNT_ORDER = \"random\"");
+
+        let mut indices = AccountSelector::from_config(&config)
+            .unwrap()
+            .select(&accounts, |_| true);
+        indices.sort();
+
+        assert_eq!(indices, [0, 1, 2, 3, 4]);
+    }
+}
@@ -1,26 +1,27 @@
-use reqwest::{Client, Proxy};
-use serde_json::{json, Value};
-use std::fmt;
+use std::{sync::Arc, time::Duration};
+
+use reqwest::{Client, Proxy, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    rate_limiter::RateLimiter,
+    schemas::{BundleStatus, InflightBundleInfo, JsonRpcResponse, RpcResponse, SimulatedBundle},
+    typedefs::{
+        BundleStatusesParams, EncodingConfig, JsonRpcRequest, SendBundleParams,
+        SendTransactionParams, SimulateBundleConfig, SimulateBundleParams,
+        SimulateBundleTransactions, TipAccountsParams, TransactionEncoding,
+    },
+};
+
+const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+const MAX_RATE_LIMITED_RETRIES: u32 = 8;
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
 
 pub struct JitoJsonRpcSDK {
     base_url: String,
     uuid: Option<String>,
     client: Client,
-}
-
-#[derive(Debug)]
-pub struct PrettyJsonValue(pub Value);
-
-impl fmt::Display for PrettyJsonValue {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", serde_json::to_string_pretty(&self.0).unwrap())
-    }
-}
-
-impl From<Value> for PrettyJsonValue {
-    fn from(value: Value) -> Self {
-        PrettyJsonValue(value)
-    }
+    rate_limiter: Option<(Arc<RateLimiter>, String)>,
 }
 
 impl JitoJsonRpcSDK {
@@ -37,103 +38,166 @@ impl JitoJsonRpcSDK {
             base_url: base_url.to_string(),
             uuid,
             client,
+            rate_limiter: None,
         }
     }
 
-    async fn send_request(
+    /// Throttles every request through `rate_limiter`, using the bucket identified by `key`.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>, key: &str) -> Self {
+        self.rate_limiter = Some((rate_limiter, key.to_string()));
+        self
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        match &self.uuid {
+            Some(uuid) => format!("{}?uuid={}", path, uuid),
+            None => path.to_string(),
+        }
+    }
+
+    async fn send_request<P: Serialize, R: DeserializeOwned>(
         &self,
         endpoint: &str,
         method: &str,
-        params: Option<Value>,
-    ) -> eyre::Result<Value, reqwest::Error> {
+        params: P,
+    ) -> eyre::Result<R> {
         let url = format!("{}{}", self.base_url, endpoint);
+        let request = JsonRpcRequest::new(method, params);
 
-        let data = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": method,
-            "params": params.unwrap_or(json!([]))
-        });
+        let mut backoff = Duration::from_secs(1);
+        let mut retries = 0;
 
-        let response = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&data)
-            .send()
-            .await?;
+        let response = loop {
+            if let Some((rate_limiter, key)) = &self.rate_limiter {
+                rate_limiter.acquire(key).await;
+            }
 
-        let body = response.json::<Value>().await?;
+            let response = self
+                .client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .json(&request)
+                .send()
+                .await
+                .map_err(|e| eyre::eyre!("{} request error: {}", method, e))?;
+
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                break response;
+            }
 
-        Ok(body)
-    }
+            retries += 1;
 
-    pub async fn get_bundle_statuses(&self, bundle_uuids: Vec<String>) -> eyre::Result<Value> {
-        let endpoint = if let Some(uuid) = &self.uuid {
-            format!("/bundles?uuid={}", uuid)
-        } else {
-            "/bundles".to_string()
-        };
+            if retries > MAX_RATE_LIMITED_RETRIES {
+                eyre::bail!(
+                    "{} is still rate limited after {} retries",
+                    method,
+                    retries - 1
+                );
+            }
 
-        // Construct the params as a list within a list
-        let params = json!([bundle_uuids]);
+            tracing::warn!("{} rate limited, backing off for {:?}", method, backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        };
 
-        self.send_request(&endpoint, "getBundleStatuses", Some(params))
+        let body = response
+            .json::<JsonRpcResponse<R>>()
             .await
-            .map_err(|e| eyre::eyre!("Request error: {}", e))
+            .map_err(|e| eyre::eyre!("{} response error: {}", method, e))?;
+
+        body.into_result()
     }
 
-    pub async fn send_bundle(
+    pub async fn get_bundle_statuses(
         &self,
-        params: Option<Value>,
-        uuid: Option<&str>,
-    ) -> eyre::Result<Value> {
-        let mut endpoint = "/bundles".to_string();
+        bundle_ids: Vec<String>,
+    ) -> eyre::Result<RpcResponse<Vec<Option<BundleStatus>>>> {
+        let params: BundleStatusesParams = (bundle_ids,);
 
-        if let Some(uuid) = uuid {
-            endpoint = format!("{}?uuid={}", endpoint, uuid);
+        self.send_request(&self.endpoint("/bundles"), "getBundleStatuses", params)
+            .await
+    }
+
+    /// Sends base58-encoded transactions as a bundle and returns the bundle id.
+    pub async fn send_bundle(&self, transactions: Vec<String>) -> eyre::Result<String> {
+        if transactions.is_empty() {
+            eyre::bail!("Bundle must contain at least one transaction");
         }
 
-        // Ensure params is an array of transactions
-        let transactions = match params {
-            Some(Value::Array(transactions)) => {
-                if transactions.is_empty() {
-                    eyre::bail!("Bundle must contain at least one transaction");
-                }
-                if transactions.len() > 5 {
-                    eyre::bail!("Bundle can contain at most 5 transactions");
-                }
-                transactions
-            }
-            _ => {
-                eyre::bail!("Invalid bundle format: expected an array of transactions")
-            }
-        };
+        if transactions.len() > MAX_BUNDLE_TRANSACTIONS {
+            eyre::bail!(
+                "Bundle can contain at most {} transactions",
+                MAX_BUNDLE_TRANSACTIONS
+            );
+        }
 
-        // Wrap the transactions array in another array
-        let params = json!([transactions]);
+        let params: SendBundleParams = (
+            transactions,
+            EncodingConfig {
+                encoding: TransactionEncoding::Base58,
+            },
+        );
 
-        // Send the wrapped transactions array
-        self.send_request(&endpoint, "sendBundle", Some(params))
+        self.send_request(&self.endpoint("/bundles"), "sendBundle", params)
             .await
-            .map_err(|e| eyre::eyre!("Request error: {}", e))
     }
 
     pub async fn get_in_flight_bundle_statuses(
         &self,
-        bundle_uuids: Vec<String>,
-    ) -> eyre::Result<Value> {
-        let endpoint = if let Some(uuid) = &self.uuid {
-            format!("/bundles?uuid={}", uuid)
-        } else {
-            "/bundles".to_string()
-        };
+        bundle_ids: Vec<String>,
+    ) -> eyre::Result<RpcResponse<Vec<Option<InflightBundleInfo>>>> {
+        let params: BundleStatusesParams = (bundle_ids,);
+
+        self.send_request(
+            &self.endpoint("/bundles"),
+            "getInflightBundleStatuses",
+            params,
+        )
+        .await
+    }
 
-        // Construct the params as a list within a list
-        let params = json!([bundle_uuids]);
+    pub async fn get_tip_accounts(&self) -> eyre::Result<Vec<String>> {
+        let params: TipAccountsParams = [];
 
-        self.send_request(&endpoint, "getInflightBundleStatuses", Some(params))
+        self.send_request(&self.endpoint("/bundles"), "getTipAccounts", params)
             .await
-            .map_err(|e| eyre::eyre!("Request error: {}", e))
+    }
+
+    /// Sends a single base58-encoded transaction and returns its signature.
+    #[allow(unused)]
+    pub async fn send_transaction(&self, transaction: String) -> eyre::Result<String> {
+        let params: SendTransactionParams = (
+            transaction,
+            EncodingConfig {
+                encoding: TransactionEncoding::Base58,
+            },
+        );
+
+        self.send_request(&self.endpoint("/transactions"), "sendTransaction", params)
+            .await
+    }
+
+    /// Simulates base58-encoded transactions as a bundle. Only Jito-compatible RPC nodes
+    /// implement this method, so `base_url` must point to one.
+    pub async fn simulate_bundle(
+        &self,
+        transactions: Vec<String>,
+    ) -> eyre::Result<RpcResponse<SimulatedBundle>> {
+        let transactions_count = transactions.len();
+
+        let params: SimulateBundleParams = (
+            SimulateBundleTransactions {
+                encoded_transactions: transactions,
+            },
+            SimulateBundleConfig {
+                pre_execution_accounts_configs: vec![None; transactions_count],
+                post_execution_accounts_configs: vec![None; transactions_count],
+                transaction_encoding: TransactionEncoding::Base58,
+                skip_sig_verify: false,
+                replace_recent_blockhash: false,
+            },
+        );
+
+        self.send_request("", "simulateBundle", params).await
     }
 }
@@ -1,3 +1,7 @@
 pub mod constants;
 pub mod jito_lib;
+pub mod rate_limiter;
+pub mod schemas;
+pub mod tip;
+pub mod typedefs;
 pub mod utils;
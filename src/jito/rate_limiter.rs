@@ -0,0 +1,62 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token-bucket limiter with a separate bucket per key (proxy), since Jito enforces its
+/// limits per IP.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> Self {
+        Self {
+            rate: requests_per_second,
+            burst: requests_per_second.max(1.0),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until a request may be sent through `key`. A non-positive rate disables limiting.
+    pub async fn acquire(&self, key: &str) {
+        if self.rate <= 0.0 {
+            return;
+        }
+
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().expect("Rate limiter lock poisoned");
+
+                let bucket = buckets
+                    .entry(key.to_string())
+                    .or_insert_with(|| TokenBucket {
+                        tokens: self.burst,
+                        last_refill: Instant::now(),
+                    });
+
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+                bucket.last_refill = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
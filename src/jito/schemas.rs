@@ -0,0 +1,379 @@
+use std::fmt;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TipFloor {
+    pub landed_tips_25th_percentile: f64,
+    pub landed_tips_50th_percentile: f64,
+    pub landed_tips_75th_percentile: f64,
+    pub landed_tips_95th_percentile: f64,
+    pub landed_tips_99th_percentile: f64,
+}
+
+impl TipFloor {
+    /// Returns the landed tip (in SOL) for `percentile`, linearly interpolated between the
+    /// percentiles reported by Jito and clamped to the 25th..99th range.
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        let points = [
+            (25.0, self.landed_tips_25th_percentile),
+            (50.0, self.landed_tips_50th_percentile),
+            (75.0, self.landed_tips_75th_percentile),
+            (95.0, self.landed_tips_95th_percentile),
+            (99.0, self.landed_tips_99th_percentile),
+        ];
+
+        if percentile <= points[0].0 {
+            return points[0].1;
+        }
+
+        for window in points.windows(2) {
+            let (lo_p, lo_tip) = window[0];
+            let (hi_p, hi_tip) = window[1];
+
+            if percentile <= hi_p {
+                return lo_tip + (hi_tip - lo_tip) * (percentile - lo_p) / (hi_p - lo_p);
+            }
+        }
+
+        points[points.len() - 1].1
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct JsonRpcResponse<T> {
+    pub result: Option<T>,
+    pub error: Option<JsonRpcError>,
+}
+
+impl<T> JsonRpcResponse<T> {
+    pub fn into_result(self) -> eyre::Result<T> {
+        match (self.result, self.error) {
+            (_, Some(error)) => Err(error.into()),
+            (Some(result), None) => Ok(result),
+            (None, None) => eyre::bail!("Response contains neither a result nor an error"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl fmt::Display for JsonRpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JSON-RPC error {}: {}", self.code, self.message)?;
+
+        if let Some(data) = &self.data {
+            write!(f, " ({})", data)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for JsonRpcError {}
+
+#[derive(Deserialize, Debug)]
+pub struct RpcResponse<T> {
+    pub value: T,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflightBundleStatus {
+    Invalid,
+    Pending,
+    Failed,
+    Landed,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct InflightBundleInfo {
+    pub status: InflightBundleStatus,
+    pub landed_slot: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmationStatus {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BundleStatus {
+    pub transactions: Vec<String>,
+    pub confirmation_status: ConfirmationStatus,
+    pub err: Result<(), Value>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SimulationSummary {
+    Succeeded,
+    Failed(SimulationFailure),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SimulationFailure {
+    pub error: Value,
+    pub tx_signature: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedTransaction {
+    pub err: Option<Value>,
+    pub logs: Option<Vec<String>>,
+    pub units_consumed: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedBundle {
+    pub summary: SimulationSummary,
+    pub transaction_results: Vec<SimulatedTransaction>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+
+    fn parse<T: DeserializeOwned>(body: &str) -> eyre::Result<T> {
+        serde_json::from_str::<JsonRpcResponse<T>>(body)?.into_result()
+    }
+
+    #[test]
+    fn bundle_statuses_parse() {
+        let body = r#"{
+            "jsonrpc": "2.0",
+            "result": {
+                "context": { "slot": 242806119 },
+                "value": [
+                    {
+                        "bundle_id": "892b79ed49138bfb3aa5441f0df6e06ef34f9ee8f3976c15b323605bae0cf51d",
+                        "transactions": [
+                            "3bC2M9fiACSjkTXZDgeNAuQ4ScTsdKGwR42ytFdhUvikqTmBheUxfsR1fDVsM5ADCMMspuwGkdm1uKbU246x5aE3",
+                            "8t9hKYEYNbLvNqiSzP96S13XF1C2f1ro271Kdf7bkZ6EpjPLuDff1ywRy4gfaGSTubsM2FeYGDoT64ZwPm1cQUt"
+                        ],
+                        "slot": 242804011,
+                        "confirmation_status": "finalized",
+                        "err": { "Ok": null }
+                    },
+                    null
+                ]
+            },
+            "id": 1
+        }"#;
+
+        let response = parse::<RpcResponse<Vec<Option<BundleStatus>>>>(body).unwrap();
+        let status = response.value[0].as_ref().unwrap();
+
+        assert_eq!(status.transactions.len(), 2);
+        assert_eq!(status.confirmation_status, ConfirmationStatus::Finalized);
+        assert!(status.err.is_ok());
+        assert!(response.value[1].is_none());
+    }
+
+    #[test]
+    fn bundle_status_errors_and_confirmations_parse() {
+        let cases = [
+            (
+                r#""processed""#,
+                r#"{ "Ok": null }"#,
+                ConfirmationStatus::Processed,
+                true,
+            ),
+            (
+                r#""confirmed""#,
+                r#"{ "Ok": null }"#,
+                ConfirmationStatus::Confirmed,
+                true,
+            ),
+            (
+                r#""confirmed""#,
+                r#"{ "Err": { "InstructionError": [1, { "Custom": 6001 }] } }"#,
+                ConfirmationStatus::Confirmed,
+                false,
+            ),
+        ];
+
+        for (confirmation, err, expected_confirmation, ok) in cases {
+            let status: BundleStatus = serde_json::from_str(&format!(
+                r#"{{ "transactions": [], "confirmation_status": {}, "err": {} }}"#,
+                confirmation, err
+            ))
+            .unwrap();
+
+            assert_eq!(status.confirmation_status, expected_confirmation, "{}", err);
+            assert_eq!(status.err.is_ok(), ok, "{}", err);
+        }
+    }
+
+    #[test]
+    fn inflight_bundle_statuses_parse() {
+        let cases = [
+            (
+                r#""Landed""#,
+                "280999028",
+                InflightBundleStatus::Landed,
+                Some(280999028),
+            ),
+            (r#""Pending""#, "null", InflightBundleStatus::Pending, None),
+            (r#""Failed""#, "null", InflightBundleStatus::Failed, None),
+            (r#""Invalid""#, "null", InflightBundleStatus::Invalid, None),
+        ];
+
+        for (status, landed_slot, expected_status, expected_slot) in cases {
+            let body = format!(
+                r#"{{
+                    "jsonrpc": "2.0",
+                    "result": {{
+                        "context": {{ "slot": 280999028 }},
+                        "value": [{{
+                            "bundle_id": "b31e5fae4923f345218403ac1ab242b46a72d4f2a38d131f474255ae88f1ec9a",
+                            "status": {},
+                            "landed_slot": {}
+                        }}]
+                    }},
+                    "id": 1
+                }}"#,
+                status, landed_slot
+            );
+
+            let response = parse::<RpcResponse<Vec<Option<InflightBundleInfo>>>>(&body).unwrap();
+            let info = response.value[0].as_ref().unwrap();
+
+            assert_eq!(info.status, expected_status, "{}", status);
+            assert_eq!(info.landed_slot, expected_slot, "{}", status);
+        }
+    }
+
+    #[test]
+    fn succeeded_simulation_parses() {
+        let body = r#"{
+            "jsonrpc": "2.0",
+            "result": {
+                "context": { "apiVersion": "1.18.22", "slot": 287383219 },
+                "value": {
+                    "summary": "succeeded",
+                    "transactionResults": [
+                        {
+                            "err": null,
+                            "logs": ["Program 11111111111111111111111111111111 invoke [1]"],
+                            "postExecutionAccounts": null,
+                            "preExecutionAccounts": null,
+                            "returnData": null,
+                            "unitsConsumed": 150
+                        },
+                        {
+                            "err": null,
+                            "logs": null,
+                            "postExecutionAccounts": null,
+                            "preExecutionAccounts": null,
+                            "returnData": null,
+                            "unitsConsumed": null
+                        }
+                    ]
+                }
+            },
+            "id": 1
+        }"#;
+
+        let bundle = parse::<RpcResponse<SimulatedBundle>>(body).unwrap().value;
+
+        assert!(matches!(bundle.summary, SimulationSummary::Succeeded));
+        assert_eq!(bundle.transaction_results[0].units_consumed, Some(150));
+        assert_eq!(bundle.transaction_results[1].units_consumed, None);
+        assert!(bundle.transaction_results[1].logs.is_none());
+    }
+
+    #[test]
+    fn failed_simulation_parses() {
+        let body = r#"{
+            "jsonrpc": "2.0",
+            "result": {
+                "context": { "apiVersion": "1.18.22", "slot": 287383219 },
+                "value": {
+                    "summary": {
+                        "failed": {
+                            "error": {
+                                "TransactionFailure": [
+                                    [1, 2, 3],
+                                    "Error processing Instruction 1: custom program error: 0x1"
+                                ]
+                            },
+                            "tx_signature": "3bC2M9fiACSjkTXZDgeNAuQ4ScTsdKGwR42ytFdhUvikqTmBheUxfsR1fDVsM5ADCMMspuwGkdm1uKbU246x5aE3"
+                        }
+                    },
+                    "transactionResults": [
+                        {
+                            "err": { "InstructionError": [1, { "Custom": 1 }] },
+                            "logs": [],
+                            "unitsConsumed": 4500
+                        }
+                    ]
+                }
+            },
+            "id": 1
+        }"#;
+
+        let bundle = parse::<RpcResponse<SimulatedBundle>>(body).unwrap().value;
+
+        let SimulationSummary::Failed(failure) = bundle.summary else {
+            panic!("Expected a failed simulation");
+        };
+        assert!(failure.tx_signature.is_some());
+        assert!(failure.error.get("TransactionFailure").is_some());
+        assert!(bundle.transaction_results[0].err.is_some());
+    }
+
+    #[test]
+    fn error_payloads_become_errors() {
+        let cases = [
+            (
+                r#"{ "jsonrpc": "2.0", "error": { "code": -32601, "message": "Method not found" }, "id": 1 }"#,
+                "JSON-RPC error -32601: Method not found",
+            ),
+            (
+                r#"{ "jsonrpc": "2.0", "error": { "code": -32097, "message": "Rate limit exceeded. Limit: 1 per second for txn requests" }, "id": 1 }"#,
+                "JSON-RPC error -32097: Rate limit exceeded. Limit: 1 per second for txn requests",
+            ),
+            (
+                r#"{ "jsonrpc": "2.0", "error": { "code": -32602, "message": "bundle contains an expired blockhash", "data": { "reason": "expired" } }, "id": 1 }"#,
+                r#"JSON-RPC error -32602: bundle contains an expired blockhash ({"reason":"expired"})"#,
+            ),
+            (
+                r#"{ "jsonrpc": "2.0", "id": 1 }"#,
+                "Response contains neither a result nor an error",
+            ),
+        ];
+
+        for (body, expected) in cases {
+            let error = parse::<String>(body).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn send_bundle_and_tip_accounts_results_parse() {
+        let bundle_id = parse::<String>(
+            r#"{ "jsonrpc": "2.0", "result": "2id3YC2jK9G5Wo2phDx4gJVAew8DcY5NAojnVuao8rkxwPYPe8cSwE5GzhEgJA2y8fVjDEo6iR6ykBvDxrTQrtpb", "id": 1 }"#,
+        )
+        .unwrap();
+        let tip_accounts = parse::<Vec<String>>(
+            r#"{ "jsonrpc": "2.0", "result": ["96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5", "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"], "id": 1 }"#,
+        )
+        .unwrap();
+
+        assert!(bundle_id.starts_with("2id3"));
+        assert_eq!(tip_accounts.len(), 2);
+    }
+}
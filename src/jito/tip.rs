@@ -0,0 +1,318 @@
+use std::{future::Future, str::FromStr};
+
+use rand::{seq::SliceRandom, thread_rng};
+use reqwest::{Method, Proxy};
+use solana_sdk::{
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+};
+
+use crate::{
+    config::Config,
+    utils::fetch::{send_http_request, RequestParams},
+};
+
+use super::{
+    constants::{JITO_BUNDLE_TIP_ACCOUNTS, JITO_TIP_FLOOR_URL},
+    jito_lib::JitoJsonRpcSDK,
+    schemas::TipFloor,
+};
+
+pub trait TipFloorSource {
+    fn get_tip_floor(&self) -> impl Future<Output = eyre::Result<TipFloor>> + Send;
+}
+
+pub struct JitoTipFloorClient {
+    proxy: Option<Proxy>,
+}
+
+impl JitoTipFloorClient {
+    pub fn new(proxy: Option<Proxy>) -> Self {
+        Self { proxy }
+    }
+}
+
+impl TipFloorSource for JitoTipFloorClient {
+    async fn get_tip_floor(&self) -> eyre::Result<TipFloor> {
+        let request_params = RequestParams {
+            url: JITO_TIP_FLOOR_URL,
+            method: Method::GET,
+            body: None::<serde_json::Value>,
+            query_args: None,
+            proxy: self.proxy.as_ref(),
+            headers: None,
+        };
+
+        let floors = send_http_request::<Vec<TipFloor>>(request_params).await?;
+
+        floors
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre::eyre!("Tip floor response is empty"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TipStrategy {
+    base_tip: u64,
+    max_tip: u64,
+    escalation: f64,
+}
+
+impl TipStrategy {
+    pub async fn new(source: &impl TipFloorSource, config: &Config) -> Self {
+        let [min_tip, max_tip] = config
+            .jito_tip_range
+            .map_or([0, u64::MAX], |range| range.map(sol_to_lamports));
+        let (min_tip, max_tip) = (min_tip.min(max_tip), min_tip.max(max_tip));
+
+        let base_tip = match config.jito_tip_percentile {
+            Some(percentile) => match source.get_tip_floor().await {
+                Ok(floor) => {
+                    let tip = sol_to_lamports(floor.percentile(percentile));
+                    tracing::info!(
+                        "Jito tip floor ({}th percentile): {} SOL",
+                        percentile,
+                        lamports_to_sol(tip)
+                    );
+                    tip
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to fetch Jito tip floor, falling back to {} SOL: {}",
+                        config.jito_tip_amount,
+                        e
+                    );
+                    sol_to_lamports(config.jito_tip_amount)
+                }
+            },
+            None => sol_to_lamports(config.jito_tip_amount),
+        }
+        .clamp(min_tip, max_tip);
+
+        tracing::info!("Jito tip: {} SOL", lamports_to_sol(base_tip));
+
+        Self {
+            base_tip,
+            max_tip,
+            escalation: config.jito_tip_escalation,
+        }
+    }
+
+    /// Tip in lamports for the given zero-based submission attempt.
+    pub fn tip_for_attempt(&self, attempt: u32) -> u64 {
+        let tip = self.base_tip as f64 * self.escalation.powi(attempt as i32);
+
+        (tip as u64).min(self.max_tip)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TipAccounts(Vec<Pubkey>);
+
+impl Default for TipAccounts {
+    fn default() -> Self {
+        Self(JITO_BUNDLE_TIP_ACCOUNTS.to_vec())
+    }
+}
+
+impl TipAccounts {
+    /// Fetches the current tip accounts from the block engine once per run, so tips follow
+    /// Jito's rotations. Falls back to the hardcoded list if the request fails or returns no
+    /// valid account.
+    pub async fn fetch(jito_sdk: &JitoJsonRpcSDK) -> Self {
+        let fetched = match jito_sdk.get_tip_accounts().await {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                tracing::warn!("Failed to fetch Jito tip accounts: {}", e);
+                vec![]
+            }
+        };
+
+        let accounts = parse_tip_accounts(&fetched);
+
+        if accounts.is_empty() {
+            tracing::warn!("Using the default Jito tip accounts");
+            return Self::default();
+        }
+
+        Self(accounts)
+    }
+
+    /// A random account of the fetched set
+    pub fn choose(&self) -> Pubkey {
+        let mut rng = thread_rng();
+        let account = *self
+            .0
+            .choose(&mut rng)
+            .expect("Tip accounts to be non-empty");
+
+        debug_assert!(self.contains(&account));
+        account
+    }
+
+    pub fn contains(&self, account: &Pubkey) -> bool {
+        self.0.contains(account)
+    }
+}
+
+/// The valid pubkeys among the fetched tip accounts
+fn parse_tip_accounts(fetched: &[String]) -> Vec<Pubkey> {
+    fetched
+        .iter()
+        .filter_map(|account| match Pubkey::from_str(account) {
+            Ok(pubkey) => Some(pubkey),
+            Err(e) => {
+                tracing::warn!("Ignoring invalid Jito tip account `{}`: {}", account, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tip floor source that returns a fixed floor, or fails when there is none
+    struct FakeTipFloor(Option<TipFloor>);
+
+    impl TipFloorSource for FakeTipFloor {
+        async fn get_tip_floor(&self) -> eyre::Result<TipFloor> {
+            self.0
+                .clone()
+                .ok_or_else(|| eyre::eyre!("Tip floor unavailable"))
+        }
+    }
+
+    fn floor() -> TipFloor {
+        TipFloor {
+            landed_tips_25th_percentile: 0.0001,
+            landed_tips_50th_percentile: 0.0002,
+            landed_tips_75th_percentile: 0.0004,
+            landed_tips_95th_percentile: 0.0012,
+            landed_tips_99th_percentile: 0.002,
+        }
+    }
+
+    async fn strategy(floor: Option<TipFloor>, extra: &str) -> TipStrategy {
+        TipStrategy::new(&FakeTipFloor(floor), &Config::for_tests(extra)).await
+    }
+
+    #[test]
+    fn percentile_interpolates_between_reported_points() {
+        let floor = floor();
+
+        for (percentile, expected) in [
+            (25.0, 0.0001),
+            (37.5, 0.00015),
+            (62.5, 0.0003),
+            (85.0, 0.0008),
+            (99.0, 0.002),
+        ] {
+            let tip = floor.percentile(percentile);
+            assert!(
+                (tip - expected).abs() < 1e-12,
+                "{percentile}: {tip} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn percentile_is_clamped_to_reported_range() {
+        let floor = floor();
+
+        assert_eq!(floor.percentile(0.0), 0.0001);
+        assert_eq!(floor.percentile(10.0), 0.0001);
+        assert_eq!(floor.percentile(99.9), 0.002);
+        assert_eq!(floor.percentile(100.0), 0.002);
+    }
+
+    #[tokio::test]
+    async fn tip_follows_the_configured_percentile() {
+        let strategy = strategy(Some(floor()), "JITO_TIP_PERCENTILE = 75").await;
+
+        assert_eq!(strategy.tip_for_attempt(0), sol_to_lamports(0.0004));
+    }
+
+    #[tokio::test]
+    async fn tip_is_clamped_to_the_configured_range() {
+        let range = "JITO_TIP_RANGE = [0.0005, 0.001]";
+
+        let low = strategy(Some(floor()), &format!("JITO_TIP_PERCENTILE = 25\n{range}")).await;
+        let high = strategy(Some(floor()), &format!("JITO_TIP_PERCENTILE = 99\n{range}")).await;
+
+        assert_eq!(low.tip_for_attempt(0), sol_to_lamports(0.0005));
+        assert_eq!(high.tip_for_attempt(0), sol_to_lamports(0.001));
+    }
+
+    #[tokio::test]
+    async fn reversed_range_is_normalized() {
+        let strategy = strategy(
+            Some(floor()),
+            "JITO_TIP_PERCENTILE = 99\nJITO_TIP_RANGE = [0.001, 0.0005]",
+        )
+        .await;
+
+        assert_eq!(strategy.tip_for_attempt(0), sol_to_lamports(0.001));
+    }
+
+    #[tokio::test]
+    async fn unavailable_floor_falls_back_to_the_fixed_tip() {
+        let strategy = strategy(None, "JITO_TIP_PERCENTILE = 75").await;
+
+        assert_eq!(strategy.tip_for_attempt(0), sol_to_lamports(0.0003));
+    }
+
+    #[tokio::test]
+    async fn without_percentile_the_fixed_tip_is_paid_unclamped() {
+        let strategy = strategy(Some(floor()), "").await;
+
+        assert_eq!(strategy.tip_for_attempt(0), sol_to_lamports(0.0003));
+        assert_eq!(strategy.tip_for_attempt(3), sol_to_lamports(0.0003));
+    }
+
+    #[tokio::test]
+    async fn tip_escalates_per_attempt_up_to_the_range_maximum() {
+        let strategy = strategy(
+            Some(floor()),
+            "JITO_TIP_PERCENTILE = 75\nJITO_TIP_RANGE = [0.0001, 0.003]\nJITO_TIP_ESCALATION = 2.0",
+        )
+        .await;
+
+        let base = sol_to_lamports(0.0004);
+
+        assert_eq!(strategy.tip_for_attempt(0), base);
+        assert_eq!(strategy.tip_for_attempt(1), base * 2);
+        assert_eq!(strategy.tip_for_attempt(2), base * 4);
+        assert_eq!(strategy.tip_for_attempt(3), sol_to_lamports(0.003));
+    }
+
+    #[test]
+    fn fetched_tip_accounts_are_trusted() {
+        let rotated = Pubkey::new_unique();
+        let fetched = vec![
+            JITO_BUNDLE_TIP_ACCOUNTS[0].to_string(),
+            rotated.to_string(),
+            "not a pubkey".to_string(),
+        ];
+
+        let accounts = TipAccounts(parse_tip_accounts(&fetched));
+
+        assert!(!JITO_BUNDLE_TIP_ACCOUNTS.contains(&rotated));
+        assert!(accounts.contains(&rotated));
+        assert_eq!(accounts.0, vec![JITO_BUNDLE_TIP_ACCOUNTS[0], rotated]);
+
+        for _ in 0..16 {
+            assert!(accounts.contains(&accounts.choose()));
+        }
+    }
+
+    #[test]
+    fn only_rotated_accounts_are_chosen_from() {
+        let rotated = Pubkey::new_unique();
+        let accounts = TipAccounts(parse_tip_accounts(&[rotated.to_string()]));
+
+        assert_eq!(accounts.choose(), rotated);
+    }
+}
@@ -0,0 +1,64 @@
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct JsonRpcRequest<'a, P: Serialize> {
+    jsonrpc: &'a str,
+    id: u64,
+    method: &'a str,
+    params: P,
+}
+
+impl<'a, P: Serialize> JsonRpcRequest<'a, P> {
+    pub fn new(method: &'a str, params: P) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id: 1,
+            method,
+            params,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionEncoding {
+    Base58,
+    #[allow(unused)]
+    Base64,
+}
+
+#[derive(Serialize, Clone, Copy)]
+pub struct EncodingConfig {
+    pub encoding: TransactionEncoding,
+}
+
+/// `sendBundle`: `[[tx, ...], {"encoding": ...}]`
+pub type SendBundleParams = (Vec<String>, EncodingConfig);
+
+/// `getBundleStatuses` and `getInflightBundleStatuses`: `[[bundle_id, ...]]`
+pub type BundleStatusesParams = (Vec<String>,);
+
+/// `getTipAccounts`: `[]`
+pub type TipAccountsParams = [(); 0];
+
+/// `sendTransaction`: `[tx, {"encoding": ...}]`
+pub type SendTransactionParams = (String, EncodingConfig);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateBundleTransactions {
+    pub encoded_transactions: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateBundleConfig {
+    pub pre_execution_accounts_configs: Vec<Option<()>>,
+    pub post_execution_accounts_configs: Vec<Option<()>>,
+    pub transaction_encoding: TransactionEncoding,
+    pub skip_sig_verify: bool,
+    pub replace_recent_blockhash: bool,
+}
+
+/// `simulateBundle`: `[{"encodedTransactions": [tx, ...]}, {...config}]`
+pub type SimulateBundleParams = (SimulateBundleTransactions, SimulateBundleConfig);
@@ -1,41 +1,164 @@
-use solana_sdk::pubkey::Pubkey;
+use std::{fmt, time::Duration};
 
-use crate::db::account::Account;
+use solana_sdk::pubkey::Pubkey;
 
-use super::jito_lib::JitoJsonRpcSDK;
+use super::{
+    jito_lib::JitoJsonRpcSDK,
+    schemas::{BundleStatus, ConfirmationStatus, InflightBundleStatus, SimulationSummary},
+};
 
+/// The simulated bundle would fail on chain, as opposed to the simulation request failing
 #[derive(Debug)]
-pub struct BundleStatus {
-    confirmation_status: Option<String>,
-    err: Option<serde_json::Value>,
-    transactions: Option<Vec<String>>,
+pub struct BundleSimulationFailed(pub String);
+
+impl fmt::Display for BundleSimulationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for BundleSimulationFailed {}
+
+/// Simulates the bundle and logs the outcome of every transaction in it. Fails with
+/// `BundleSimulationFailed` if any of them would fail on chain, and with any other error if
+/// the simulation itself couldn't be run. Otherwise returns the compute units each
+/// transaction consumed.
+pub async fn check_bundle_simulation(
+    simulation_sdk: &JitoJsonRpcSDK,
+    transactions: Vec<String>,
+    pubkey: &Pubkey,
+) -> eyre::Result<Vec<Option<u64>>> {
+    let simulation = simulation_sdk.simulate_bundle(transactions).await?.value;
+
+    for (index, result) in simulation.transaction_results.iter().enumerate() {
+        let logs = result.logs.as_deref().unwrap_or_default().join("\n");
+
+        match &result.err {
+            Some(err) => tracing::error!(
+                "{}: Simulated transaction #{} failed: {}\n{}",
+                pubkey,
+                index,
+                err,
+                logs
+            ),
+            None => tracing::debug!(
+                "{}: Simulated transaction #{} consumed {} CU\n{}",
+                pubkey,
+                index,
+                result.units_consumed.unwrap_or_default(),
+                logs
+            ),
+        }
+    }
+
+    let failure = match simulation.summary {
+        SimulationSummary::Failed(failure) => Some(format!(
+            "Bundle simulation failed at {}: {}",
+            failure
+                .tx_signature
+                .as_deref()
+                .unwrap_or("unknown transaction"),
+            failure.error
+        )),
+        SimulationSummary::Succeeded => simulation
+            .transaction_results
+            .iter()
+            .position(|result| result.err.is_some())
+            .map(|index| format!("Simulated transaction #{} failed", index)),
+    };
+
+    match failure {
+        Some(failure) => Err(BundleSimulationFailed(failure).into()),
+        None => Ok(simulation
+            .transaction_results
+            .iter()
+            .map(|result| result.units_consumed)
+            .collect()),
+    }
 }
 
+/// Polls the in-flight status of a bundle until it lands, fails or turns out invalid, and
+/// returns that status. A bundle that is still pending after all polls is returned as
+/// `Pending`.
+pub async fn wait_for_landing(
+    jito_sdk: &JitoJsonRpcSDK,
+    bundle_id: &str,
+    pubkey: &Pubkey,
+) -> InflightBundleStatus {
+    let max_retries = 10;
+    let retry_delay = Duration::from_secs(5);
+
+    for attempt in 1..=max_retries {
+        match jito_sdk
+            .get_in_flight_bundle_statuses(vec![bundle_id.to_string()])
+            .await
+        {
+            Ok(response) => {
+                if let Some(info) = response.value.into_iter().flatten().next() {
+                    match info.status {
+                        InflightBundleStatus::Landed => {
+                            tracing::info!(
+                                "{}: Bundle landed in slot {}",
+                                pubkey,
+                                info.landed_slot.unwrap_or_default()
+                            );
+                            return InflightBundleStatus::Landed;
+                        }
+                        InflightBundleStatus::Pending => {}
+                        InflightBundleStatus::Failed => {
+                            tracing::warn!("{}: Bundle {} failed", pubkey, bundle_id);
+                            return InflightBundleStatus::Failed;
+                        }
+                        InflightBundleStatus::Invalid => {
+                            tracing::warn!(
+                                "{}: Bundle {} is invalid or expired",
+                                pubkey,
+                                bundle_id
+                            );
+                            return InflightBundleStatus::Invalid;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!("{}: Error checking bundle status: {}", pubkey, e);
+            }
+        }
+
+        if attempt < max_retries {
+            tokio::time::sleep(retry_delay).await;
+        }
+    }
+
+    InflightBundleStatus::Pending
+}
+
+/// Waits for the bundle to be finalized and returns the signatures of its transactions.
 pub async fn check_final_bundle_status(
     jito_sdk: &JitoJsonRpcSDK,
-    bundle_uuid: &str,
-    account: Account,
-) -> eyre::Result<()> {
+    bundle_id: &str,
+    pubkey: &Pubkey,
+) -> eyre::Result<Vec<String>> {
     let max_retries = 10;
-    let retry_delay = tokio::time::Duration::from_secs(5);
+    let retry_delay = Duration::from_secs(5);
 
     for attempt in 1..=max_retries {
         let status_response = jito_sdk
-            .get_bundle_statuses(vec![bundle_uuid.to_string()])
+            .get_bundle_statuses(vec![bundle_id.to_string()])
             .await?;
-        let bundle_status = get_bundle_status(&status_response)?;
 
-        match bundle_status.confirmation_status.as_deref() {
-            Some("confirmed") => {
-                check_transaction_error(&account.get_pubkey(), &bundle_status)?;
-            }
-            Some("finalized") => {
-                check_transaction_error(&account.get_pubkey(), &bundle_status)?;
-                print_transaction_url(&account.get_pubkey(), &bundle_status);
-                return Ok(());
+        if let Some(bundle_status) = status_response.value.into_iter().flatten().next() {
+            match bundle_status.confirmation_status {
+                ConfirmationStatus::Processed => {}
+                ConfirmationStatus::Confirmed => {
+                    check_transaction_error(pubkey, &bundle_status)?;
+                }
+                ConfirmationStatus::Finalized => {
+                    check_transaction_error(pubkey, &bundle_status)?;
+                    print_transaction_url(pubkey, &bundle_status);
+                    return Ok(bundle_status.transactions);
+                }
             }
-            Some(_) => {}
-            None => {}
         }
 
         if attempt < max_retries {
@@ -45,59 +168,34 @@ pub async fn check_final_bundle_status(
 
     eyre::bail!(
         "{}: Failed to get finalized status after {} attempts",
-        account.get_pubkey(),
+        pubkey,
         max_retries
     )
 }
 
-pub fn get_bundle_status(status_response: &serde_json::Value) -> eyre::Result<BundleStatus> {
-    status_response
-        .get("result")
-        .and_then(|result| result.get("value"))
-        .and_then(|value| value.as_array())
-        .and_then(|statuses| statuses.first())
-        .ok_or_else(|| eyre::eyre!("Failed to parse bundle status"))
-        .map(|bundle_status| BundleStatus {
-            confirmation_status: bundle_status
-                .get("confirmation_status")
-                .and_then(|s| s.as_str())
-                .map(String::from),
-            err: bundle_status.get("err").cloned(),
-            transactions: bundle_status
-                .get("transactions")
-                .and_then(|t| t.as_array())
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str().map(String::from))
-                        .collect()
-                }),
-        })
-}
-
 pub fn check_transaction_error(pubkey: &Pubkey, bundle_status: &BundleStatus) -> eyre::Result<()> {
-    if let Some(err) = &bundle_status.err {
-        if err["Ok"].is_null() {
+    match &bundle_status.err {
+        Ok(()) => {
             tracing::info!("{}: Transaction executed without errors.", pubkey);
             Ok(())
-        } else {
-            tracing::error!("{}: Transaction encountered an error: {:?}", pubkey, err);
+        }
+        Err(err) => {
+            tracing::error!("{}: Transaction encountered an error: {}", pubkey, err);
             eyre::bail!("{}: Transaction encountered an error", pubkey)
         }
-    } else {
-        Ok(())
     }
 }
 
 pub fn print_transaction_url(pubkey: &Pubkey, bundle_status: &BundleStatus) {
-    if let Some(transactions) = &bundle_status.transactions {
-        for tx_id in transactions {
-            tracing::info!(
-                "{}: Transaction confirmed: https://solscan.io/tx/{}",
-                pubkey,
-                tx_id
-            );
-        }
-    } else {
+    if bundle_status.transactions.is_empty() {
         tracing::warn!("{}: No transactions found in the bundle status.", pubkey);
     }
+
+    for tx_id in &bundle_status.transactions {
+        tracing::info!(
+            "{}: Transaction confirmed: https://solscan.io/tx/{}",
+            pubkey,
+            tx_id
+        );
+    }
 }
@@ -1,245 +1,1334 @@
-use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
+use reqwest::Proxy;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    compute_budget,
+    hash::Hash,
     instruction::Instruction,
     native_token::{lamports_to_sol, sol_to_lamports},
     pubkey::Pubkey,
-    signature::Signature,
+    signature::{Keypair, Signature},
     signer::Signer,
     transaction::{Transaction, VersionedTransaction},
 };
 use tokio::task::JoinSet;
+use tracing::Instrument;
 
 use crate::{
-    config::Config,
-    db::{account::Account, database::Database},
-    jito::{jito_lib::JitoJsonRpcSDK, utils::check_final_bundle_status},
+    config::{Config, SubmissionMode},
+    db::{
+        account::{accounts_span, Account, ClaimAttempt, ClaimAttemptStatus},
+        database::Database,
+    },
+    jito::{
+        constants::JITO_BLOCK_ENGINE_URL,
+        jito_lib::JitoJsonRpcSDK,
+        rate_limiter::RateLimiter,
+        schemas::InflightBundleStatus,
+        tip::{JitoTipFloorClient, TipAccounts, TipStrategy},
+        utils::{
+            check_bundle_simulation, check_final_bundle_status, wait_for_landing,
+            BundleSimulationFailed,
+        },
+    },
     onchain::{
-        constants::{ME_PUBKEY, TOKEN_PROGRAM_ID},
+        compute_budget::{get_cu_price, ComputeBudget},
+        constants::{
+            ASSOCIATED_TOKEN_PROGRAM_ID, MEMO_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_2022_PROGRAM_ID,
+            TOKEN_PROGRAM_ID,
+        },
         crypto::get_wallet,
-        derive::derive_ata,
         ixs::Instructions,
-        typedefs::CreateAtaArgs,
+        split::{get_split_transfer_ixs, split_amount, SplitRule},
+        token::{get_token_balance, get_token_balance_change, get_token_mint},
+        tx::{fits_in_packet, send_and_confirm_tx},
+        typedefs::TokenMint,
     },
 };
 
-use super::prepare_txs::get_claim_txs;
+use super::{
+    prepare_txs::get_claim_txs,
+    tasks::{handle_task_result, TaskResult},
+};
+
+const MAX_CLAIMS_PER_BUNDLE: usize = 4;
+
+struct ClaimContext {
+    config: Config,
+    provider: RpcClient,
+    mint: TokenMint,
+    token_account_rent: u64,
+    cu_price: u64,
+    tip_strategy: TipStrategy,
+    tip_accounts: TipAccounts,
+    rate_limiter: Arc<RateLimiter>,
+    jito_failures: AtomicU32,
+}
+
+impl ClaimContext {
+    fn jito_provider(&self, proxy_url: &str) -> JitoJsonRpcSDK {
+        let uuid = Some(self.config.jito_uuid.clone()).filter(|uuid| !uuid.is_empty());
+        let proxy = Proxy::all(proxy_url).expect("Proxy to be valid");
+
+        JitoJsonRpcSDK::new(JITO_BLOCK_ENGINE_URL, uuid, &proxy)
+            .with_rate_limiter(Arc::clone(&self.rate_limiter), proxy_url)
+    }
+
+    fn simulation_provider(&self, proxy_url: &str) -> Option<JitoJsonRpcSDK> {
+        if self.config.jito_simulation_rpc_url.is_empty() {
+            return None;
+        }
+
+        let proxy = Proxy::all(proxy_url).expect("Proxy to be valid");
+
+        Some(JitoJsonRpcSDK::new(
+            &self.config.jito_simulation_rpc_url,
+            None,
+            &proxy,
+        ))
+    }
+
+    fn use_rpc(&self) -> bool {
+        match self.config.claim_submission_mode {
+            SubmissionMode::Jito => false,
+            SubmissionMode::Rpc => true,
+            SubmissionMode::Auto => {
+                self.jito_failures.load(Ordering::Relaxed) >= self.config.jito_failures_before_rpc
+            }
+        }
+    }
+}
+
+/// A bundle that wouldn't succeed on chain. Not a Jito failure, so it doesn't count towards
+/// switching to RPC submission.
+#[derive(Debug)]
+struct SimulationError(String);
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+/// Jito submission ended and its claims definitely didn't land, so they may be sent another way
+#[derive(Debug)]
+struct NotLandedError(String);
+
+impl fmt::Display for NotLandedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NotLandedError {}
+
+/// A claim transaction signed by its wallet, ready to be put into a bundle.
+#[derive(Clone)]
+struct SignedClaim {
+    index: usize,
+    wallet: Pubkey,
+    destination: Option<String>,
+    memo: Option<String>,
+    rent_destination: Option<String>,
+    split_rules: Vec<SplitRule>,
+    tx: String,
+    signature: Signature,
+    allocation: u64,
+    recent_blockhash: Hash,
+    /// Follow-up transactions sent along with this claim, any of which may have landed
+    follow_ups: Vec<Signature>,
+}
 
-pub async fn claim_me(db: Database, config: &Config) -> eyre::Result<()> {
-    let config = Arc::new(config.clone());
+pub async fn claim_me(mut db: Database, config: &Config) -> eyre::Result<()> {
+    if config.pack_bundles && !config.use_external_fee_pay {
+        eyre::bail!("Packing claims into shared bundles requires an external fee payer");
+    }
 
-    let mut accounts: Vec<Account> = db.0.clone();
+    let selected = db.select(config, |_| true)?;
+    let mut accounts: Vec<Account> = selected.iter().map(|&index| db.0[index].clone()).collect();
 
     let mut join_set = JoinSet::new();
 
-    let txs = get_claim_txs(&mut accounts, &config).await?;
+    let proxy = Proxy::all(&config.me_proxy_url).expect("Invalid proxy URL");
 
-    for (index, account) in accounts.into_iter().enumerate() {
-        let config_clone = Arc::clone(&config);
-        let txs_for_account = txs[index].clone();
+    let tip_floor_client = JitoTipFloorClient::new(Some(proxy));
+    let tip_strategy = TipStrategy::new(&tip_floor_client, config).await;
+
+    let rate_limiter = Arc::new(RateLimiter::new(config.jito_requests_per_second));
+
+    let provider = RpcClient::new_with_timeout_and_commitment(
+        config.solana_rpc_url.clone(),
+        Duration::from_secs(60),
+        CommitmentConfig::processed(),
+    );
+
+    let mint = get_token_mint(&provider, &Pubkey::from_str(&config.mint)?).await?;
+
+    let token_account_rent = provider
+        .get_minimum_balance_for_rent_exemption(mint.token_account_len())
+        .await?;
+
+    let fee_accounts: Vec<Pubkey> = std::iter::once(mint.mint)
+        .chain(accounts.iter().map(|a| a.get_pubkey()))
+        .collect();
+    let cu_price = get_cu_price(&provider, config, &fee_accounts).await;
+
+    let txs = get_claim_txs(&mut accounts, cu_price, config).await?;
+
+    let mut claims = vec![];
+
+    for (&index, (account, txs_for_account)) in selected.iter().zip(accounts.iter().zip(txs)) {
+        match sign_claims(index, account, txs_for_account, config) {
+            Ok(signed) => claims.push(signed),
+            Err(e) => account.span().in_scope(|| {
+                tracing::error!("{}: Failed to sign claims: {}", account.get_pubkey(), e)
+            }),
+        }
+    }
 
-        join_set.spawn(process_account(account, txs_for_account, config_clone));
+    let claims: Vec<Vec<SignedClaim>> = claims.into_iter().filter(|c| !c.is_empty()).collect();
+
+    // The follow-up transactions land right after the claims, so they compete for the same
+    // distributor accounts
+    let distributor_accounts = distributor_accounts(claims.iter().flatten());
+    let cu_price = match distributor_accounts.is_empty() {
+        true => cu_price,
+        false => {
+            let fee_accounts: Vec<Pubkey> = distributor_accounts
+                .into_iter()
+                .chain(fee_accounts)
+                .collect();
+            get_cu_price(&provider, config, &fee_accounts).await
+        }
+    };
+
+    let mut context = ClaimContext {
+        config: config.clone(),
+        provider,
+        mint,
+        token_account_rent,
+        cu_price,
+        tip_strategy,
+        tip_accounts: TipAccounts::default(),
+        rate_limiter,
+        jito_failures: AtomicU32::new(0),
+    };
+
+    context.tip_accounts = TipAccounts::fetch(&context.jito_provider(&config.me_proxy_url)).await;
+
+    // Every wallet belongs to exactly one task, so no two tasks hand back the same account
+    let tasks: Vec<Vec<Vec<SignedClaim>>> = if config.pack_bundles {
+        let payer_pubkey = get_wallet(&config.external_fee_payer_secret)?.pubkey();
+        pack_claims(claims, &payer_pubkey, &context).await?
+    } else {
+        claims
+            .into_iter()
+            .map(|claims| claims.into_iter().map(|claim| vec![claim]).collect())
+            .collect()
+    };
+
+    let context = Arc::new(context);
+
+    for bundles in tasks {
+        let context_clone = Arc::clone(&context);
+
+        let mut task_accounts: Vec<(usize, Account)> = vec![];
+        for claim in bundles.iter().flatten() {
+            if !task_accounts.iter().any(|(index, _)| *index == claim.index) {
+                task_accounts.push((claim.index, db.0[claim.index].clone()));
+            }
+        }
+
+        let span = accounts_span(task_accounts.iter().map(|(_, account)| account));
+
+        join_set.spawn(process_claims(task_accounts, bundles, context_clone).instrument(span));
 
         if join_set.len() >= config.parallelism {
             if let Some(result) = join_set.join_next().await {
-                match result {
-                    Ok(Ok(())) => {}
-                    Ok(Err(e)) => {
-                        tracing::error!("Task failed with error: {}", e);
-                    }
-                    Err(e) => {
-                        tracing::error!("Task panicked or failed to join: {}", e);
-                    }
-                }
+                handle_task_result(&mut db, result).await;
             }
         }
     }
 
     while let Some(result) = join_set.join_next().await {
-        match result {
-            Ok(Ok(())) => {}
-            Ok(Err(e)) => {
-                tracing::error!("Task failed with error: {}", e);
+        handle_task_result(&mut db, result).await;
+    }
+
+    Ok(())
+}
+
+/// Programs the claim transactions call besides the well-known ones, i.e. the ME distributor,
+/// along with the accounts they write to
+fn distributor_accounts<'a>(claims: impl Iterator<Item = &'a SignedClaim>) -> Vec<Pubkey> {
+    let known_programs = [
+        compute_budget::ID,
+        SYSTEM_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        TOKEN_2022_PROGRAM_ID,
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        MEMO_PROGRAM_ID,
+    ];
+
+    let mut accounts = vec![];
+
+    for claim in claims {
+        let Some(claim_tx) = solana_sdk::bs58::decode(&claim.tx)
+            .into_vec()
+            .ok()
+            .and_then(|bytes| bincode::deserialize::<VersionedTransaction>(&bytes).ok())
+        else {
+            continue;
+        };
+
+        let message = &claim_tx.message;
+        let keys = message.static_account_keys();
+        // Writable non-signers follow the signers among the static keys
+        let header = message.header();
+
+        for ix in message.instructions() {
+            let Some(program_id) = keys.get(ix.program_id_index as usize) else {
+                continue;
+            };
+            if known_programs.contains(program_id) {
+                continue;
             }
-            Err(e) => {
-                tracing::error!("Task panicked or failed to join: {}", e);
+
+            let writable = ix
+                .accounts
+                .iter()
+                .map(|&index| index as usize)
+                .filter(|&index| {
+                    index >= header.num_required_signatures as usize
+                        && index < keys.len() - header.num_readonly_unsigned_accounts as usize
+                });
+
+            for pubkey in std::iter::once(program_id).chain(writable.filter_map(|i| keys.get(i))) {
+                if !accounts.contains(pubkey) {
+                    accounts.push(*pubkey);
+                }
             }
         }
     }
 
-    Ok(())
+    accounts
 }
 
-async fn get_ixs(
-    allocation: u64,
-    wallet_pubkey: &Pubkey,
-    cex_pubkey: Option<&str>,
+fn sign_claims(
+    index: usize,
+    account: &Account,
+    txs: Vec<HashMap<String, u64>>,
+    config: &Config,
+) -> eyre::Result<Vec<SignedClaim>> {
+    let wallet = account.keypair()?;
+    let destination = account.get_token_destination(config);
+
+    let mut claims = vec![];
+
+    for tx_map in txs {
+        for (tx_base58, allocation) in tx_map {
+            let tx_bytes = solana_sdk::bs58::decode(tx_base58).into_vec()?;
+            let mut claim_tx = bincode::deserialize::<VersionedTransaction>(&tx_bytes)?;
+
+            let new_signature: Signature = wallet.sign_message(&claim_tx.message.serialize());
+            claim_tx.signatures[0] = new_signature;
+
+            claims.push(SignedClaim {
+                index,
+                wallet: wallet.pubkey(),
+                destination: destination.map(|(address, _)| address.to_string()),
+                memo: destination.and_then(|(_, memo)| memo).map(String::from),
+                rent_destination: account.get_rent_destination(config).map(String::from),
+                split_rules: account.get_split_rules(&config.split_rules).to_vec(),
+                tx: solana_sdk::bs58::encode(bincode::serialize(&claim_tx)?).into_string(),
+                signature: new_signature,
+                allocation,
+                recent_blockhash: *claim_tx.message.recent_blockhash(),
+                follow_ups: vec![],
+            });
+        }
+    }
+
+    Ok(claims)
+}
+
+/// Greedily packs the wallets' claims into bundles of up to `MAX_CLAIMS_PER_BUNDLE` claim
+/// transactions, as long as the shared follow-up transaction still fits into a single packet.
+/// A wallet's claims always stay together: either in one shared bundle, or, if they don't fit
+/// into one, in consecutive bundles of their own. Returns the bundles grouped into tasks.
+async fn pack_claims(
+    wallet_claims: Vec<Vec<SignedClaim>>,
+    payer_pubkey: &Pubkey,
+    context: &ClaimContext,
+) -> eyre::Result<Vec<Vec<Vec<SignedClaim>>>> {
+    let mut tasks = vec![];
+    let mut current: Vec<SignedClaim> = vec![];
+
+    for claims in wallet_claims {
+        let mut candidate = current.clone();
+        candidate.extend(claims.iter().cloned());
+
+        if bundle_fits(&candidate, payer_pubkey, context).await? {
+            current = candidate;
+            continue;
+        }
+
+        if !current.is_empty() {
+            tasks.push(vec![std::mem::take(&mut current)]);
+        }
+
+        if bundle_fits(&claims, payer_pubkey, context).await? {
+            current = claims;
+            continue;
+        }
+
+        let mut bundles = vec![];
+        let mut bundle: Vec<SignedClaim> = vec![];
+
+        for claim in claims {
+            bundle.push(claim);
+
+            if bundle.len() > 1 && !bundle_fits(&bundle, payer_pubkey, context).await? {
+                let claim = bundle.pop().expect("Bundle to be non-empty");
+                bundles.push(std::mem::replace(&mut bundle, vec![claim]));
+            }
+        }
+
+        bundles.push(bundle);
+        tasks.push(bundles);
+    }
+
+    if !current.is_empty() {
+        tasks.push(vec![current]);
+    }
+
+    tracing::info!(
+        "Packed claims into {} bundles",
+        tasks.iter().map(Vec::len).sum::<usize>()
+    );
+
+    Ok(tasks)
+}
+
+async fn bundle_fits(
+    claims: &[SignedClaim],
+    payer_pubkey: &Pubkey,
+    context: &ClaimContext,
+) -> eyre::Result<bool> {
+    if claims.len() > MAX_CLAIMS_PER_BUNDLE {
+        return Ok(false);
+    }
+
+    let tip_account = context.tip_accounts.choose();
+    let jito_tip = context.tip_strategy.tip_for_attempt(0);
+
+    let ixs = get_bundle_ixs(claims, payer_pubkey, &tip_account, jito_tip, context).await?;
+    let ixs = ComputeBudget::estimate(&ixs, context.cu_price).apply(&ixs);
+
+    fits_in_packet(&ixs, payer_pubkey)
+}
+
+/// Instructions of the follow-up transaction: every claim's instructions plus a single tip.
+/// It can't be simulated on its own before the claims land, so callers size its CU limit
+/// from an estimate or from the bundle simulation.
+async fn get_bundle_ixs(
+    claims: &[SignedClaim],
     payer_pubkey: &Pubkey,
-    config: &Arc<Config>,
+    tip_account: &Pubkey,
+    jito_tip: u64,
+    context: &ClaimContext,
 ) -> eyre::Result<Vec<Instruction>> {
+    let mut ixs = get_claims_ixs(claims, payer_pubkey, context).await?;
+
+    ixs.push(Instructions::jito_tip_ix(
+        payer_pubkey,
+        tip_account,
+        jito_tip,
+    ));
+
+    Ok(ixs)
+}
+
+fn claim_fee(allocation: u64) -> u64 {
+    sol_to_lamports(lamports_to_sol(allocation) * (0.01 + 0.01 + 0.01 + 0.02))
+}
+
+/// Follow-up instructions of every claim, wallet by wallet. A wallet's ATA is drained and
+/// closed once, after its last claim, even if it has several claims.
+async fn get_claims_ixs(
+    claims: &[SignedClaim],
+    payer_pubkey: &Pubkey,
+    context: &ClaimContext,
+) -> eyre::Result<Vec<Instruction>> {
+    let mut wallets: Vec<Pubkey> = vec![];
+    for claim in claims {
+        if !wallets.contains(&claim.wallet) {
+            wallets.push(claim.wallet);
+        }
+    }
+
     let mut ixs = vec![];
 
-    let (wallet_token_ata, _) = derive_ata(wallet_pubkey, &ME_PUBKEY, &TOKEN_PROGRAM_ID);
+    for wallet in wallets {
+        let wallet_claims: Vec<&SignedClaim> = claims
+            .iter()
+            .filter(|claim| claim.wallet == wallet)
+            .collect();
 
-    let rent = sol_to_lamports(lamports_to_sol(allocation) * (0.01 + 0.01 + 0.01 + 0.02));
+        ixs.extend(get_wallet_ixs(&wallet_claims, payer_pubkey, context).await?);
+    }
 
-    let tip_ix = Instructions::tip_ix(
-        &wallet_token_ata,
-        wallet_pubkey,
-        payer_pubkey,
-        config.jito_tip_amount,
-        rent,
-    );
+    Ok(ixs)
+}
 
-    ixs.extend_from_slice(&tip_ix);
+/// Follow-up instructions of one wallet's claims
+async fn get_wallet_ixs(
+    claims: &[&SignedClaim],
+    payer_pubkey: &Pubkey,
+    context: &ClaimContext,
+) -> eyre::Result<Vec<Instruction>> {
+    let config = &context.config;
+    let wallet_pubkey = &claims[0].wallet;
+    let wallet_token_ata = context.mint.ata(wallet_pubkey);
 
-    if config.withdraw_to_cex && cex_pubkey.is_some() {
-        let cex_pubkey = Pubkey::from_str(cex_pubkey.unwrap())?;
+    // Closing requires an empty account, so tokens it held before the claims go along with
+    // the first transfer
+    let mut existing_balance = match config.close_ata_after_claim && config.withdraw_to_cex {
+        true => get_token_balance(
+            &context.provider,
+            &wallet_token_ata,
+            CommitmentConfig::confirmed(),
+        )
+        .await?
+        .unwrap_or_default(),
+        false => 0,
+    };
 
-        let (cex_token_ata, _) = derive_ata(&cex_pubkey, &ME_PUBKEY, &TOKEN_PROGRAM_ID);
+    let mut ixs = vec![];
+    let mut withdrawn = false;
 
-        let create_ata_args = CreateAtaArgs {
-            funding_address: *payer_pubkey,
-            associated_account_address: cex_token_ata,
-            wallet_address: cex_pubkey,
-            token_mint_address: ME_PUBKEY,
-            token_program_id: TOKEN_PROGRAM_ID,
-            instruction: 1,
-        };
+    for claim in claims {
+        let allocation = claim.allocation;
+        let rent = claim_fee(allocation);
 
-        ixs.push(Instructions::create_ata(create_ata_args));
+        ixs.extend(Instructions::fee_ix(
+            &context.mint,
+            &wallet_token_ata,
+            wallet_pubkey,
+            payer_pubkey,
+            rent,
+        ));
+
+        if let Some(cex_pubkey) = claim
+            .destination
+            .as_deref()
+            .filter(|_| config.withdraw_to_cex)
+        {
+            let cex_pubkey = Pubkey::from_str(cex_pubkey)?;
 
-        let transfer_ix = spl_token::instruction::transfer_checked(
-            &TOKEN_PROGRAM_ID,
+            let withdraw_amount = allocation - rent + std::mem::take(&mut existing_balance);
+
+            ixs.extend(get_split_transfer_ixs(
+                &context.mint,
+                wallet_pubkey,
+                payer_pubkey,
+                &cex_pubkey,
+                claim.memo.as_deref(),
+                &claim.split_rules,
+                withdraw_amount,
+            )?);
+
+            withdrawn = true;
+        }
+    }
+
+    if withdrawn && config.close_ata_after_claim {
+        let refund_pubkey = match &claims[0].rent_destination {
+            Some(address) => Pubkey::from_str(address)?,
+            None => *payer_pubkey,
+        };
+
+        let close_ix = Instructions::close_account(
+            &context.mint.token_program_id,
             &wallet_token_ata,
-            &ME_PUBKEY,
-            &cex_token_ata,
             wallet_pubkey,
-            &[wallet_pubkey],
-            allocation - rent,
-            6u8,
-        )?;
+            payer_pubkey,
+            &refund_pubkey,
+            context.token_account_rent,
+        );
 
-        ixs.push(transfer_ix);
+        ixs.extend_from_slice(&close_ix);
     }
 
     Ok(ixs)
 }
 
-async fn process_account(
-    account: Account,
-    txs: Vec<HashMap<std::string::String, u64>>,
-    config: Arc<Config>,
+async fn process_claims(
+    mut accounts: Vec<(usize, Account)>,
+    bundles: Vec<Vec<SignedClaim>>,
+    context: Arc<ClaimContext>,
+) -> Vec<TaskResult> {
+    let mut results: HashMap<usize, eyre::Result<()>> = HashMap::new();
+    let mut claimed: HashMap<usize, u64> = HashMap::new();
+    // Each account's landed claim and follow-up transactions
+    let mut landed_txs: HashMap<usize, (Vec<Signature>, Vec<Signature>)> = HashMap::new();
+
+    for mut bundle_claims in bundles {
+        let indices: Vec<usize> = bundle_claims.iter().map(|claim| claim.index).collect();
+
+        match submit_claims(&mut bundle_claims, &mut accounts, &context).await {
+            Ok(landed) => {
+                for claim in &bundle_claims {
+                    if landed.contains(&claim.signature.to_string()) {
+                        *claimed.entry(claim.index).or_default() +=
+                            claim.allocation - claim_fee(claim.allocation);
+
+                        let (claim_txs, follow_ups) = landed_txs.entry(claim.index).or_default();
+                        claim_txs.push(claim.signature);
+                        for follow_up in &claim.follow_ups {
+                            if landed.contains(&follow_up.to_string())
+                                && !follow_ups.contains(follow_up)
+                            {
+                                follow_ups.push(*follow_up);
+                            }
+                        }
+                    } else {
+                        results.insert(
+                            claim.index,
+                            Err(eyre::eyre!(
+                                "{}: Claim transaction is missing from the landed bundle",
+                                claim.wallet
+                            )),
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                for index in indices {
+                    results.insert(index, Err(eyre::eyre!("{}", e)));
+                }
+                break;
+            }
+        }
+    }
+
+    for (index, account) in accounts.iter_mut() {
+        if let (Some(amount), None) = (claimed.get(index), results.get(index)) {
+            let (claim_txs, follow_ups) = landed_txs.remove(index).unwrap_or_default();
+
+            if let Err(e) = verify_claim(account, *amount, &claim_txs, &follow_ups, &context).await
+            {
+                results.insert(*index, Err(e));
+            }
+        }
+    }
+
+    accounts
+        .into_iter()
+        .map(|(index, account)| {
+            let result = results.remove(&index).unwrap_or(Ok(()));
+            (index, account, result)
+        })
+        .collect()
+}
+
+/// Checks the claimed tokens on chain at finalized commitment: they stay in the wallet's ME
+/// token account, or end up in the CEX one when withdrawing. Stores the balances once they
+/// cover `claimed` and clears any earlier flag, or flags the account if they fall short.
+/// Checks the landed transactions' token balance changes at finalized commitment: the claims
+/// must have credited the wallet with `claimed`, and when withdrawing, the follow-ups must
+/// have sent the CEX its part. Balances held or swept before or after don't matter. The
+/// verified amounts are stored, a mismatch flags the account.
+async fn verify_claim(
+    account: &mut Account,
+    claimed: u64,
+    claim_txs: &[Signature],
+    follow_ups: &[Signature],
+    context: &ClaimContext,
 ) -> eyre::Result<()> {
-    let jito_provider = JitoJsonRpcSDK::new(
-        "https://mainnet.block-engine.jito.wtf/api/v1",
-        None,
-        &account.proxy(),
+    let max_retries = 10;
+    let retry_delay = Duration::from_secs(5);
+
+    let wallet_pubkey = account.get_pubkey();
+    let mint = &context.mint.mint;
+
+    let cex_pubkey = match account.get_token_destination(&context.config) {
+        Some((cex_address, _)) if context.config.withdraw_to_cex => {
+            Some(Pubkey::from_str(cex_address)?)
+        }
+        _ => None,
+    };
+
+    let expected_cex = match cex_pubkey {
+        Some(_) => {
+            let split_rules = account.get_split_rules(&context.config.split_rules);
+            let parts = split_amount(claimed, split_rules)?;
+            parts.last().copied()
+        }
+        None => None,
+    };
+
+    let mut outcome = String::from("no landed transactions were read");
+
+    for attempt in 1..=max_retries {
+        match landed_changes(
+            claim_txs,
+            follow_ups,
+            &wallet_pubkey,
+            cex_pubkey.as_ref(),
+            mint,
+            context,
+        )
+        .await
+        {
+            Ok((me_received, cex_received)) => {
+                let verified = me_received >= claimed
+                    && match (expected_cex, cex_received) {
+                        (Some(expected), Some(received)) => received >= expected,
+                        (Some(_), None) => false,
+                        (None, _) => true,
+                    };
+
+                if verified {
+                    tracing::info!(
+                        "{}: Verified claim on chain, ME received: {}, CEX received: {:?}",
+                        wallet_pubkey,
+                        me_received,
+                        cex_received
+                    );
+
+                    account.set_verified_balances(me_received, cex_received);
+                    account.clear_flag();
+
+                    return Ok(());
+                }
+
+                outcome = format!(
+                    "ME received: {}, CEX received: {:?} of {:?}",
+                    me_received, cex_received, expected_cex
+                );
+            }
+            Err(e) => outcome = e.to_string(),
+        }
+
+        if attempt < max_retries {
+            tokio::time::sleep(retry_delay).await;
+        }
+    }
+
+    let flag = format!(
+        "Landed transactions don't match the claim: expected {}, {}",
+        claimed, outcome
     );
 
-    let wallet = account.keypair()?;
+    account.set_flag(flag.clone());
+
+    eyre::bail!("{}: {}", wallet_pubkey, flag)
+}
+
+/// Tokens the wallet received from its claims and the CEX received from the follow-ups.
+/// The CEX amount is `None` without a CEX or without a landed follow-up.
+async fn landed_changes(
+    claim_txs: &[Signature],
+    follow_ups: &[Signature],
+    wallet_pubkey: &Pubkey,
+    cex_pubkey: Option<&Pubkey>,
+    mint: &Pubkey,
+    context: &ClaimContext,
+) -> eyre::Result<(u64, Option<u64>)> {
+    let mut me_received = 0;
+    for signature in claim_txs {
+        me_received +=
+            get_token_balance_change(&context.provider, signature, mint, wallet_pubkey).await?;
+    }
+
+    let cex_received = match cex_pubkey {
+        Some(cex_pubkey) if !follow_ups.is_empty() => {
+            let mut received = 0;
+            for signature in follow_ups {
+                received +=
+                    get_token_balance_change(&context.provider, signature, mint, cex_pubkey)
+                        .await?;
+            }
+            Some(received)
+        }
+        _ => None,
+    };
+
+    let to_amount = |change: i128| u64::try_from(change.max(0)).unwrap_or(u64::MAX);
+
+    Ok((to_amount(me_received), cex_received.map(to_amount)))
+}
+
+async fn submit_claims(
+    claims: &mut Vec<SignedClaim>,
+    accounts: &mut [(usize, Account)],
+    context: &ClaimContext,
+) -> eyre::Result<Vec<String>> {
+    if context.use_rpc() {
+        return send_claims_via_rpc(claims, accounts, context).await;
+    }
+
+    match send_claims(claims, accounts, context).await {
+        Ok(landed) => {
+            context.jito_failures.store(0, Ordering::Relaxed);
+            Ok(landed)
+        }
+        // Only claims that definitely didn't land through Jito may be sent through the RPC
+        Err(e) if e.downcast_ref::<NotLandedError>().is_none() => Err(e),
+        Err(e) => {
+            let failures = context.jito_failures.fetch_add(1, Ordering::Relaxed) + 1;
+
+            if !context.use_rpc() || claims.is_empty() {
+                return Err(e);
+            }
+
+            tracing::warn!(
+                "{}. Jito failed {} times in a row, falling back to RPC",
+                e,
+                failures
+            );
+
+            let landed = refresh_claims(claims, accounts, context).await?;
+
+            if !landed.is_empty() {
+                return Ok(landed);
+            }
+
+            if claims.is_empty() {
+                eyre::bail!("{}: No claim left to send via RPC", e);
+            }
+
+            send_claims_via_rpc(claims, accounts, context).await
+        }
+    }
+}
+
+/// Sends every claim transaction through the RPC, then a transaction with their follow-up
+/// instructions, prioritized with a compute budget instead of a Jito tip.
+async fn send_claims_via_rpc(
+    claims: &mut Vec<SignedClaim>,
+    accounts: &mut [(usize, Account)],
+    context: &ClaimContext,
+) -> eyre::Result<Vec<String>> {
+    let ClaimContext {
+        config, provider, ..
+    } = context;
+
+    // The claims were fetched at the start of the run and may have expired since
+    if !blockhash_valid(claims, context).await {
+        let landed = refresh_claims(claims, accounts, context).await?;
+
+        if !landed.is_empty() {
+            return Ok(landed);
+        }
+
+        if claims.is_empty() {
+            eyre::bail!("No claim left to send via RPC");
+        }
+    }
+
+    for claim in claims.iter() {
+        let tx_bytes = solana_sdk::bs58::decode(&claim.tx).into_vec()?;
+        let claim_tx = bincode::deserialize::<VersionedTransaction>(&tx_bytes)?;
+
+        tracing::info!("{}: Sending claim transaction via RPC", claim.wallet);
+
+        send_and_confirm_tx(provider, claim_tx).await?;
+
+        // Recorded right away, so a failing follow-up doesn't hide a landed claim. RPC
+        // attempts carry the claim signature instead of a bundle id.
+        record_attempt(
+            accounts,
+            std::slice::from_ref(claim),
+            &claim.signature.to_string(),
+            0,
+            ClaimAttemptStatus::Landed,
+        );
+    }
+
+    let wallets = accounts
+        .iter()
+        .map(|(_, account)| account.keypair())
+        .collect::<eyre::Result<Vec<Keypair>>>()?;
 
     let payer_kp = match config.use_external_fee_pay {
         true => get_wallet(&config.external_fee_payer_secret)?,
-        false => wallet.insecure_clone(),
+        false => wallets[0].insecure_clone(),
     };
 
-    let signing_keypairs = match config.use_external_fee_pay {
-        true => vec![&payer_kp, &wallet],
-        false => vec![&wallet],
+    let instructions = get_claims_ixs(claims, &payer_kp.pubkey(), context).await?;
+
+    let compute_budget = ComputeBudget::simulate(
+        provider,
+        &instructions,
+        &payer_kp.pubkey(),
+        context.cu_price,
+    )
+    .await;
+    let instructions = compute_budget.apply(&instructions);
+
+    let mut signing_keypairs = vec![&payer_kp];
+    signing_keypairs.extend(wallets.iter().filter(|w| {
+        w.pubkey() != payer_kp.pubkey() && claims.iter().any(|claim| claim.wallet == w.pubkey())
+    }));
+
+    let (recent_blockhash, _) = provider
+        .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+        .await?;
+
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer_kp.pubkey()),
+        &signing_keypairs,
+        recent_blockhash,
+    );
+
+    let follow_up = tx.signatures[0];
+    for claim in claims.iter_mut() {
+        claim.follow_ups.push(follow_up);
+    }
+
+    send_and_confirm_tx(provider, tx).await?;
+
+    Ok(claims
+        .iter()
+        .map(|claim| claim.signature.to_string())
+        .chain(std::iter::once(follow_up.to_string()))
+        .collect())
+}
+
+/// Sends the claim transactions as one bundle, followed by a transaction that carries their
+/// follow-up instructions and the Jito tip. Bundles that don't land are resubmitted with an
+/// escalated tip; once the claims' blockhash expires, they are replaced with fresh ones from
+/// the ME API. Returns the signatures of the landed transactions.
+async fn send_claims(
+    claims: &mut Vec<SignedClaim>,
+    accounts: &mut [(usize, Account)],
+    context: &ClaimContext,
+) -> eyre::Result<Vec<String>> {
+    let ClaimContext {
+        config,
+        tip_strategy,
+        tip_accounts,
+        ..
+    } = context;
+
+    let proxy_url = accounts[0].1.get_proxy_url().to_string();
+    let jito_provider = context.jito_provider(&proxy_url);
+    let simulation_provider = context.simulation_provider(&proxy_url);
+
+    let wallets = accounts
+        .iter()
+        .map(|(_, account)| account.keypair())
+        .collect::<eyre::Result<Vec<Keypair>>>()?;
+
+    let payer_kp = match config.use_external_fee_pay {
+        true => get_wallet(&config.external_fee_payer_secret)?,
+        false => wallets[0].insecure_clone(),
     };
 
-    for tx_map in txs {
-        for (tx_base58, allocation) in tx_map.iter() {
-            let tx_bytes = solana_sdk::bs58::decode(tx_base58).into_vec()?;
-            let mut claim_tx = bincode::deserialize::<VersionedTransaction>(&tx_bytes)?;
+    let pubkey = claims[0].wallet;
 
-            let new_signature: Signature = wallet.sign_message(&claim_tx.message.serialize());
-            claim_tx.signatures[0] = new_signature;
+    let mut attempt = 0;
+    // Whether the current claim transactions were sent to Jito at least once
+    let mut submitted = false;
 
-            let serialized_claim_tx =
-                solana_sdk::bs58::encode(bincode::serialize(&claim_tx)?).into_string();
+    loop {
+        let jito_tip = tip_strategy.tip_for_attempt(attempt);
 
-            let instructions = get_ixs(
-                *allocation,
-                &wallet.pubkey(),
-                account.get_cex_address(),
-                &payer_kp.pubkey(),
-                &config,
-            )
-            .await?;
+        let tip_account = tip_accounts.choose();
 
-            let recent_blockhash = claim_tx.message.recent_blockhash();
+        let instructions =
+            get_bundle_ixs(claims, &payer_kp.pubkey(), &tip_account, jito_tip, context).await?;
 
+        let mut signing_keypairs = vec![&payer_kp];
+        signing_keypairs.extend(wallets.iter().filter(|w| {
+            w.pubkey() != payer_kp.pubkey() && claims.iter().any(|claim| claim.wallet == w.pubkey())
+        }));
+
+        let sign_inner_tx = |compute_budget: ComputeBudget| -> eyre::Result<(String, Signature)> {
             let inner_tx = Transaction::new_signed_with_payer(
-                &instructions,
+                &compute_budget.apply(&instructions),
                 Some(&payer_kp.pubkey()),
                 &signing_keypairs,
-                *recent_blockhash,
+                claims[0].recent_blockhash,
+            );
+
+            Ok((
+                solana_sdk::bs58::encode(bincode::serialize(&inner_tx)?).into_string(),
+                inner_tx.signatures[0],
+            ))
+        };
+
+        let (inner_tx, mut follow_up) =
+            sign_inner_tx(ComputeBudget::estimate(&instructions, context.cu_price))?;
+
+        let mut bundle: Vec<String> = claims.iter().map(|claim| claim.tx.clone()).collect();
+        bundle.push(inner_tx);
+
+        if let Some(simulation_provider) = &simulation_provider {
+            match check_bundle_simulation(simulation_provider, bundle.clone(), &pubkey).await {
+                // Size the follow-up transaction from what it actually consumed
+                Ok(units) => {
+                    if let Some(Some(units)) = units.last() {
+                        let units = u32::try_from(*units).unwrap_or(u32::MAX);
+                        if let Some(inner_tx) = bundle.last_mut() {
+                            (*inner_tx, follow_up) =
+                                sign_inner_tx(ComputeBudget::from_units(units, context.cu_price))?;
+                        }
+                    }
+                }
+                Err(e) if e.downcast_ref::<BundleSimulationFailed>().is_some() => {
+                    for (_, account) in accounts.iter_mut() {
+                        account.set_flag(e.to_string());
+                    }
+                    return Err(
+                        SimulationError(format!("{}: Skipping bundle, {}", pubkey, e)).into(),
+                    );
+                }
+                // An unreachable or unsupported simulation endpoint says nothing about the
+                // bundle, so it goes out with the estimated CU limit
+                Err(e) => tracing::warn!(
+                    "{}: Failed to simulate bundle, submitting without simulation: {}",
+                    pubkey,
+                    e
+                ),
+            }
+        }
+
+        for claim in claims.iter_mut() {
+            claim.follow_ups.push(follow_up);
+        }
+
+        let bundle_uuid = match jito_provider.send_bundle(bundle).await {
+            Ok(bundle_uuid) => bundle_uuid,
+            Err(e) if !submitted => return Err(NotLandedError(format!("{}: {}", pubkey, e)).into()),
+            Err(e) => return finish_unlanded(claims, context, format!("{}: {}", pubkey, e)).await,
+        };
+        submitted = true;
+
+        // Wallets sharing a bundle split its tip evenly
+        let in_bundle = |index: usize| claims.iter().any(|claim| claim.index == index);
+        let bundle_wallets = accounts
+            .iter()
+            .filter(|(index, _)| in_bundle(*index))
+            .count();
+        let tip_share = jito_tip / bundle_wallets.max(1) as u64;
+        for (_, account) in accounts.iter_mut().filter(|(index, _)| in_bundle(*index)) {
+            account.set_jito_tip(tip_share);
+        }
+
+        tracing::info!(
+            "Sent bundle with {} claims and {} SOL tip: https://explorer.jito.wtf/bundle/{}",
+            claims.len(),
+            lamports_to_sol(jito_tip),
+            bundle_uuid
+        );
+
+        let landing = wait_for_landing(&jito_provider, &bundle_uuid, &pubkey).await;
+
+        if landing == InflightBundleStatus::Landed {
+            record_attempt(
+                accounts,
+                claims,
+                &bundle_uuid,
+                tip_share,
+                ClaimAttemptStatus::Landed,
             );
+            return check_final_bundle_status(&jito_provider, &bundle_uuid, &pubkey).await;
+        }
 
-            let serialized_inner_tx =
-                solana_sdk::bs58::encode(bincode::serialize(&inner_tx)?).into_string();
+        let blockhash_valid = blockhash_valid(claims, context).await;
 
-            let bundle = serde_json::json!([serialized_claim_tx, serialized_inner_tx]);
+        let status = match (landing, blockhash_valid) {
+            (InflightBundleStatus::Failed | InflightBundleStatus::Invalid, _) => {
+                ClaimAttemptStatus::Failed
+            }
+            (_, true) => ClaimAttemptStatus::NotLanded,
+            (_, false) => ClaimAttemptStatus::Dropped,
+        };
 
-            let uuid = None;
+        record_attempt(accounts, claims, &bundle_uuid, tip_share, status);
 
-            let response = jito_provider.send_bundle(Some(bundle), uuid).await?;
+        attempt += 1;
 
-            let bundle_uuid = response["result"]
-                .as_str()
-                .ok_or_else(|| eyre::eyre!("Failed to get bundle UUID from response"))?;
+        if attempt >= config.claim_max_attempts {
+            let reason = format!("{}: Bundle didn't land after {} attempts", pubkey, attempt);
+            return finish_unlanded(claims, context, reason).await;
+        }
 
-            tracing::info!(
-                "Sent bundle: https://explorer.jito.wtf/bundle/{}",
+        if !blockhash_valid {
+            tracing::warn!(
+                "{}: Bundle {} didn't land before its blockhash expired",
+                pubkey,
                 bundle_uuid
             );
 
-            let max_retries = 10;
-            let retry_delay = Duration::from_secs(5);
-
-            for attempt in 1..=max_retries {
-                let status_response = jito_provider
-                    .get_in_flight_bundle_statuses(vec![bundle_uuid.to_string()])
-                    .await?;
-
-                if let Some(result) = status_response.get("result") {
-                    if let Some(value) = result.get("value") {
-                        if let Some(statuses) = value.as_array() {
-                            if let Some(bundle_status) = statuses.first() {
-                                if let Some(status) = bundle_status.get("status") {
-                                    match status.as_str() {
-                                        Some("Landed") => {
-                                            return check_final_bundle_status(
-                                                &jito_provider,
-                                                bundle_uuid,
-                                                account.to_owned(),
-                                            )
-                                            .await;
-                                        }
-                                        Some("Pending") => {}
-                                        Some(_) => {}
-                                        None => {}
-                                    }
-                                }
-                            }
-                        }
-                    }
-                } else if let Some(error) = status_response.get("error") {
-                    tracing::error!(
-                        "{}: Error checking bundle status: {:?}",
-                        wallet.pubkey(),
-                        error
-                    );
-                }
+            let landed = refresh_claims(claims, accounts, context).await?;
 
-                if attempt < max_retries {
-                    tokio::time::sleep(retry_delay).await;
-                }
+            if !landed.is_empty() {
+                return Ok(landed);
             }
+
+            if claims.is_empty() {
+                eyre::bail!("{}: No claim left to resubmit", pubkey);
+            }
+
+            submitted = false;
         }
+
+        tracing::warn!(
+            "{}: Bundle didn't land, resubmitting with {} SOL tip",
+            pubkey,
+            lamports_to_sol(tip_strategy.tip_for_attempt(attempt))
+        );
     }
+}
 
-    Ok(())
+/// Ends a Jito submission whose bundles didn't land. Earlier bundles may still land until the
+/// claims' blockhash expires, so this waits for that first: the claims then either turn out to
+/// have landed after all, or definitely didn't and may be sent another way.
+async fn finish_unlanded(
+    claims: &[SignedClaim],
+    context: &ClaimContext,
+    reason: String,
+) -> eyre::Result<Vec<String>> {
+    if !wait_for_blockhash_expiry(&context.provider, &claims[0].recent_blockhash).await {
+        eyre::bail!("{}, but may still land", reason);
+    }
+
+    let landed = landed_signatures(claims, context).await?;
+
+    if !landed.is_empty() {
+        tracing::info!("{}: An earlier bundle landed after all", claims[0].wallet);
+        return Ok(landed);
+    }
+
+    Err(NotLandedError(reason).into())
+}
+
+/// Polls until `blockhash` expires. Returns `false` if it is still valid after all polls.
+async fn wait_for_blockhash_expiry(provider: &RpcClient, blockhash: &Hash) -> bool {
+    let max_retries = 30;
+    let retry_delay = Duration::from_secs(5);
+
+    for attempt in 1..=max_retries {
+        match provider
+            .is_blockhash_valid(blockhash, CommitmentConfig::processed())
+            .await
+        {
+            Ok(false) => return true,
+            Ok(true) => {}
+            Err(e) => tracing::warn!("Failed to check blockhash {}: {}", blockhash, e),
+        }
+
+        if attempt < max_retries {
+            tokio::time::sleep(retry_delay).await;
+        }
+    }
+
+    false
+}
+
+/// Signatures of the claim transactions that were executed on chain
+/// Signatures of the claims and follow-up transactions that landed successfully
+async fn landed_signatures(
+    claims: &[SignedClaim],
+    context: &ClaimContext,
+) -> eyre::Result<Vec<String>> {
+    let mut signatures: Vec<Signature> = claims.iter().map(|claim| claim.signature).collect();
+    for follow_up in claims.iter().flat_map(|claim| &claim.follow_ups) {
+        if !signatures.contains(follow_up) {
+            signatures.push(*follow_up);
+        }
+    }
+
+    let mut landed = vec![];
+
+    // `getSignatureStatuses` accepts at most 256 signatures
+    for chunk in signatures.chunks(256) {
+        let statuses = context
+            .provider
+            .get_signature_statuses_with_history(chunk)
+            .await?
+            .value;
+
+        landed.extend(
+            chunk
+                .iter()
+                .zip(statuses)
+                .filter(|(_, status)| status.as_ref().is_some_and(|status| status.err.is_none()))
+                .map(|(signature, _)| signature.to_string()),
+        );
+    }
+
+    // Only a landed claim makes its follow-ups meaningful
+    if !claims
+        .iter()
+        .any(|claim| landed.contains(&claim.signature.to_string()))
+    {
+        return Ok(vec![]);
+    }
+
+    Ok(landed)
+}
+
+/// Whether the claims' blockhash can still land, assumed so if the check itself fails
+async fn blockhash_valid(claims: &[SignedClaim], context: &ClaimContext) -> bool {
+    let mut blockhashes: Vec<&Hash> = claims.iter().map(|claim| &claim.recent_blockhash).collect();
+    blockhashes.dedup();
+
+    for blockhash in blockhashes {
+        match context
+            .provider
+            .is_blockhash_valid(blockhash, CommitmentConfig::processed())
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => return false,
+            Err(e) => tracing::warn!(
+                "{}: Failed to check the claims' blockhash: {}",
+                claims[0].wallet,
+                e
+            ),
+        }
+    }
+
+    true
+}
+
+fn record_attempt(
+    accounts: &mut [(usize, Account)],
+    claims: &[SignedClaim],
+    bundle_id: &str,
+    jito_tip: u64,
+    status: ClaimAttemptStatus,
+) {
+    for (index, account) in accounts.iter_mut() {
+        if claims.iter().any(|claim| claim.index == *index) {
+            account.push_claim_attempt(ClaimAttempt {
+                bundle_id: bundle_id.to_string(),
+                jito_tip,
+                status,
+            });
+        }
+    }
+}
+
+/// Replaces claims whose blockhash expired with freshly signed ones from the ME API. If any
+/// of them was executed on chain, their bundle landed after all: the claims are kept and the
+/// landed signatures returned instead. Claims are only replaced once every fresh one is in.
+async fn refresh_claims(
+    claims: &mut Vec<SignedClaim>,
+    accounts: &[(usize, Account)],
+    context: &ClaimContext,
+) -> eyre::Result<Vec<String>> {
+    let landed = landed_signatures(claims, context).await?;
+
+    if !landed.is_empty() {
+        tracing::info!("{}: Claim already landed on chain", claims[0].wallet);
+        return Ok(landed);
+    }
+
+    let mut refreshed = vec![];
+
+    for claim in claims.iter() {
+        let (_, account) = accounts
+            .iter()
+            .find(|(index, _)| *index == claim.index)
+            .expect("Claim account to be in the task");
+
+        let txs = get_claim_txs(&mut [account.clone()], context.cu_price, &context.config).await?;
+
+        let fresh = sign_claims(
+            claim.index,
+            account,
+            txs.into_iter().flatten().collect(),
+            &context.config,
+        )?
+        .into_iter()
+        .find(|fresh| fresh.allocation == claim.allocation);
+
+        match fresh {
+            Some(fresh) => refreshed.push(fresh),
+            None => tracing::info!("{}: No claim left to resubmit", claim.wallet),
+        }
+    }
+
+    *claims = refreshed;
+
+    Ok(vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        instruction::AccountMeta,
+        message::{v0, VersionedMessage},
+    };
+
+    use super::*;
+
+    fn signed_claim(ixs: &[Instruction], wallet: &Keypair) -> SignedClaim {
+        let message =
+            v0::Message::try_compile(&wallet.pubkey(), ixs, &[], Hash::default()).unwrap();
+        let tx = VersionedTransaction::try_new(VersionedMessage::V0(message), &[wallet]).unwrap();
+
+        SignedClaim {
+            index: 0,
+            wallet: wallet.pubkey(),
+            destination: None,
+            memo: None,
+            rent_destination: None,
+            split_rules: vec![],
+            tx: solana_sdk::bs58::encode(bincode::serialize(&tx).unwrap()).into_string(),
+            signature: tx.signatures[0],
+            allocation: 0,
+            recent_blockhash: Hash::default(),
+            follow_ups: vec![],
+        }
+    }
+
+    #[test]
+    fn distributor_accounts_skip_known_programs_and_readonly_accounts() {
+        let wallet = Keypair::new();
+        let distributor = Pubkey::new_unique();
+        let claim_status = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+
+        let claim = signed_claim(
+            &[
+                ComputeBudget::new(200_000, 1).instructions()[0].clone(),
+                Instruction {
+                    program_id: distributor,
+                    accounts: vec![
+                        AccountMeta::new(wallet.pubkey(), true),
+                        AccountMeta::new(claim_status, false),
+                        AccountMeta::new_readonly(readonly, false),
+                    ],
+                    data: vec![],
+                },
+                solana_sdk::system_instruction::transfer(&wallet.pubkey(), &claim_status, 1),
+            ],
+            &wallet,
+        );
+
+        assert_eq!(
+            distributor_accounts([&claim, &claim].into_iter()),
+            vec![distributor, claim_status]
+        );
+    }
 }
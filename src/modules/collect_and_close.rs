@@ -1,28 +1,45 @@
-use std::{str::FromStr, time::Duration};
+use std::{collections::HashSet, str::FromStr, sync::Arc, time::Duration};
 
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::signer::Signer;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
     instruction::Instruction,
+    message::Message,
     native_token::{lamports_to_sol, sol_to_lamports},
-    program_pack::Pack,
     pubkey::Pubkey,
+    signature::Keypair,
     transaction::Transaction,
 };
+use tokio::task::JoinSet;
+use tracing::Instrument;
 
-use crate::onchain::constants::ME_PUBKEY;
 use crate::onchain::crypto::get_wallet;
 use crate::{
-    config::Config,
-    db::{account::Account, database::Database},
+    config::{Config, SolCollectMode},
+    db::{
+        account::{accounts_span, Account},
+        database::Database,
+    },
     onchain::{
-        constants::TOKEN_PROGRAM_ID, derive::derive_ata, ixs::Instructions,
-        tx::send_and_confirm_tx, typedefs::CreateAtaArgs,
+        compute_budget::{get_cu_price, ComputeBudget},
+        constants::ASSOCIATED_TOKEN_PROGRAM_ID,
+        ixs::Instructions,
+        split::get_split_transfer_ixs,
+        token::{
+            get_owned_token_accounts, get_token_mint, is_missing_ata_closed, unpack_token_amount,
+        },
+        tx::{fits_in_packet, send_and_confirm_tx},
+        typedefs::{OwnedTokenAccount, TokenMint},
     },
-    utils::misc::pretty_sleep,
+    utils::misc::random_in_range,
 };
 
+use super::tasks::{handle_task_result, TaskResult};
+
+/// Enough for the first simulation of a self-paid collect transaction, which has no priority fee
+const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
 pub async fn collect_and_close(mut db: Database, config: &Config) -> eyre::Result<()> {
     let provider = RpcClient::new_with_timeout_and_commitment(
         config.solana_rpc_url.clone(),
@@ -30,137 +47,483 @@ pub async fn collect_and_close(mut db: Database, config: &Config) -> eyre::Resul
         CommitmentConfig::processed(),
     );
 
-    while let Some(account) =
-        db.get_random_account_with_filter(|a| !a.get_collected_sol() || !a.get_closed_ata())
-    {
-        if let Err(e) = process_account(&provider, account, config).await {
-            tracing::error!("{}", e);
-        } else {
-            account.set_closed_ata(true);
-            account.set_collected_sol(true);
-            db.update().await;
+    let mint = get_token_mint(&provider, &Pubkey::from_str(&config.mint)?).await?;
+
+    let selected = db.select(config, |a| !a.get_collected_sol() || !a.get_closed_ata())?;
+
+    let fee_accounts: Vec<Pubkey> = std::iter::once(mint.mint)
+        .chain(selected.iter().map(|&index| db.0[index].get_pubkey()))
+        .collect();
+    let cu_price = get_cu_price(&provider, config, &fee_accounts).await;
+
+    if config.collect_sol && config.sol_collect_mode == SolCollectMode::Reserve {
+        let reserve = sol_to_lamports(config.sol_reserve);
+        let rent_exempt_minimum = provider.get_minimum_balance_for_rent_exemption(0).await?;
+
+        if reserve != 0 && reserve < rent_exempt_minimum {
+            eyre::bail!(
+                "SOL_RESERVE has to be 0 or at least the rent-exempt minimum of {} SOL",
+                lamports_to_sol(rent_exempt_minimum)
+            );
+        }
+    }
+
+    if config.batch_collect {
+        return collect_batched(&mut db, &selected, &provider, &mint, cu_price, config).await;
+    }
+
+    let provider = Arc::new(provider);
+    let shared_config = Arc::new(config.clone());
+
+    let mut join_set = JoinSet::new();
+    let mut processed = 0;
+
+    for &index in &selected {
+        let account = db.0[index].clone();
+        let span = account.span();
+
+        join_set.spawn(
+            collect_task(
+                index,
+                account,
+                Arc::clone(&provider),
+                mint,
+                cu_price,
+                Arc::clone(&shared_config),
+            )
+            .instrument(span),
+        );
+
+        if join_set.len() >= config.parallelism {
+            if let Some(result) = join_set.join_next().await {
+                handle_task_result(&mut db, result.map(|result| vec![result])).await;
+
+                processed += 1;
+                tracing::info!("Processed {}/{} accounts", processed, selected.len());
+            }
+        }
+    }
+
+    while let Some(result) = join_set.join_next().await {
+        handle_task_result(&mut db, result.map(|result| vec![result])).await;
+
+        processed += 1;
+        tracing::info!("Processed {}/{} accounts", processed, selected.len());
+    }
+
+    Ok(())
+}
+
+/// Collects from the `selected` wallets, packing as many of them as fit into each
+/// transaction. The external payer covers the fees and signs along with every wallet.
+async fn collect_batched(
+    db: &mut Database,
+    selected: &[usize],
+    provider: &RpcClient,
+    mint: &TokenMint,
+    cu_price: u64,
+    config: &Config,
+) -> eyre::Result<()> {
+    if !config.use_external_fee_pay {
+        eyre::bail!("Batched collection requires an external fee payer");
+    }
+
+    let payer_kp = get_wallet(&config.external_fee_payer_secret)?;
+
+    let mut batch: Vec<(usize, CollectIxs)> = vec![];
+
+    for &index in selected {
+        let account = &db.0[index];
+
+        let ixs = async {
+            tracing::info!("Wallet address: `{}`", account.get_pubkey());
+
+            if let Err(e) =
+                collect_other_tokens(provider, account, mint, &payer_kp, cu_price, config).await
+            {
+                tracing::error!(
+                    "{}: Failed to collect other tokens: {}",
+                    account.get_pubkey(),
+                    e
+                );
+            }
+
+            get_ixs(provider, account, mint, &payer_kp.pubkey(), 0, config)
+                .await
+                .inspect_err(|e| tracing::error!("{}: {}", account.get_pubkey(), e))
+                .ok()
+        }
+        .instrument(account.span())
+        .await;
+
+        let Some(ixs) = ixs else {
+            continue;
         };
 
-        pretty_sleep(config.claim_sleep_range).await;
+        batch.push((index, ixs));
+
+        if batch_fits(&batch, &payer_kp.pubkey(), cu_price)? {
+            continue;
+        }
+
+        let last = batch.pop().expect("Batch to be non-empty");
+        send_batch(
+            db,
+            provider,
+            &payer_kp,
+            std::mem::take(&mut batch),
+            cu_price,
+        )
+        .await;
+
+        batch.push(last);
+
+        if !batch_fits(&batch, &payer_kp.pubkey(), cu_price)? {
+            tracing::error!(
+                "{}: Instructions don't fit into a single transaction",
+                db.0[batch[0].0].get_pubkey()
+            );
+            batch.clear();
+        }
+    }
+
+    if !batch.is_empty() {
+        send_batch(db, provider, &payer_kp, batch, cu_price).await;
     }
 
     Ok(())
 }
 
+fn batch_fits(
+    batch: &[(usize, CollectIxs)],
+    payer_pubkey: &Pubkey,
+    cu_price: u64,
+) -> eyre::Result<bool> {
+    let ixs: Vec<Instruction> = batch
+        .iter()
+        .flat_map(|(_, collect)| collect.ixs.clone())
+        .collect();
+    let ixs = ComputeBudget::estimate(&ixs, cu_price).apply(&ixs);
+
+    fits_in_packet(&ixs, payer_pubkey)
+}
+
+/// Sends one batched transaction and updates its wallets' flags once it's confirmed.
+async fn send_batch(
+    db: &mut Database,
+    provider: &RpcClient,
+    payer_kp: &Keypair,
+    batch: Vec<(usize, CollectIxs)>,
+    cu_price: u64,
+) {
+    let span = accounts_span(batch.iter().map(|(index, _)| &db.0[*index]));
+
+    if let Err(e) = try_send_batch(db, provider, payer_kp, &batch, cu_price)
+        .instrument(span.clone())
+        .await
+    {
+        span.in_scope(|| {
+            tracing::error!(
+                "Failed to collect a batch of {} wallets: {}",
+                batch.len(),
+                e
+            )
+        });
+        return;
+    }
+
+    for (index, collect) in batch {
+        collect.apply_flags(&mut db.0[index]);
+    }
+
+    db.update().await;
+}
+
+async fn try_send_batch(
+    db: &Database,
+    provider: &RpcClient,
+    payer_kp: &Keypair,
+    batch: &[(usize, CollectIxs)],
+    cu_price: u64,
+) -> eyre::Result<()> {
+    let mut wallets = vec![];
+    let mut instructions = vec![];
+
+    for (index, collect) in batch {
+        if collect.ixs.is_empty() {
+            continue;
+        }
+
+        wallets.push(db.0[*index].keypair()?);
+        instructions.extend_from_slice(&collect.ixs);
+    }
+
+    if instructions.is_empty() {
+        return Ok(());
+    }
+
+    tracing::info!(
+        "Collecting from {} wallets in one transaction",
+        wallets.len()
+    );
+
+    let compute_budget =
+        ComputeBudget::simulate(provider, &instructions, &payer_kp.pubkey(), cu_price).await;
+    let instructions = compute_budget.apply(&instructions);
+
+    let mut signing_keypairs = vec![payer_kp];
+    signing_keypairs.extend(wallets.iter());
+
+    let (recent_blockhash, _) = provider
+        .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+        .await?;
+
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer_kp.pubkey()),
+        &signing_keypairs,
+        recent_blockhash,
+    );
+
+    send_and_confirm_tx(provider, tx).await
+}
+
+/// Instructions collecting from one wallet, and which of its db flags they settle.
+struct CollectIxs {
+    ixs: Vec<Instruction>,
+    /// The token account gets closed, or it was closed before
+    closes_ata: bool,
+    /// The SOL gets swept, or there is too little of it to sweep
+    collects_sol: bool,
+}
+
+impl CollectIxs {
+    fn apply_flags(&self, account: &mut Account) {
+        if self.closes_ata {
+            account.set_closed_ata(true);
+        }
+
+        if self.collects_sol {
+            account.set_collected_sol(true);
+        }
+
+        // Whatever went wrong with the account before is settled once it's fully collected
+        if account.is_collected() {
+            account.clear_flag();
+        }
+    }
+}
+
 async fn get_ixs(
     provider: &RpcClient,
-    wallet_pubkey: &Pubkey,
-    collector_pubkey: Option<&str>,
+    account: &Account,
+    mint: &TokenMint,
     payer_pubkey: &Pubkey,
+    tx_fee: u64,
     config: &Config,
-) -> eyre::Result<Option<Vec<Instruction>>> {
-    let mut ixs = vec![];
+) -> eyre::Result<CollectIxs> {
+    let wallet_pubkey = &account.get_pubkey();
+    let split_rules = account.get_split_rules(&config.split_rules);
 
-    let (wallet_token_ata, _) = derive_ata(wallet_pubkey, &ME_PUBKEY, &TOKEN_PROGRAM_ID);
-    let token_ata_exist = provider.get_account_data(&wallet_token_ata).await.is_ok();
+    let self_paid = payer_pubkey == wallet_pubkey;
 
-    let mut should_add_rent = false;
+    let refund_pubkey = get_refund_pubkey(account, payer_pubkey, config)?;
 
-    let rent = provider
-        .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
-        .await?;
+    let mut ixs = vec![];
+    let mut closes_ata = true;
 
-    if token_ata_exist {
-        if payer_pubkey == wallet_pubkey {
-            should_add_rent = true;
-        }
+    // Lamports the wallet gets back and spends within the transaction, besides the SOL transfer
+    let mut refunded = 0;
+    let mut spent = 0;
 
-        let token_account = provider
-            .get_token_account_balance(&wallet_token_ata)
-            .await?;
+    let wallet_token_ata = mint.ata(wallet_pubkey);
+    let token_account = provider
+        .get_account_with_commitment(&wallet_token_ata, CommitmentConfig::confirmed())
+        .await?
+        .value;
 
-        let token_account_balance = token_account.amount.parse::<u64>()?;
+    if let Some(token_account) = token_account {
+        let token_account_balance = unpack_token_amount(&token_account.data)?;
 
         if token_account_balance != 0 && config.collect_me {
-            let user = Pubkey::from_str(collector_pubkey.unwrap())?;
-
-            let (collector_token_ata, _) = derive_ata(&user, &ME_PUBKEY, &TOKEN_PROGRAM_ID);
-            let collector_token_ata_exist = provider
-                .get_account_data(&collector_token_ata)
-                .await
-                .is_ok();
-
-            if !collector_token_ata_exist {
-                let create_ata_args = CreateAtaArgs {
-                    funding_address: *payer_pubkey,
-                    associated_account_address: collector_token_ata,
-                    wallet_address: user,
-                    token_mint_address: ME_PUBKEY,
-                    token_program_id: TOKEN_PROGRAM_ID,
-                    instruction: 0,
-                };
-
-                ixs.push(Instructions::create_ata(create_ata_args));
-            }
+            let (token_destination, token_memo) =
+                parse_destination(account.get_token_destination(config), "token")?;
 
-            ixs.push(spl_token::instruction::transfer_checked(
-                &TOKEN_PROGRAM_ID,
-                &wallet_token_ata,
-                &ME_PUBKEY,
-                &collector_token_ata,
+            ixs.extend(get_split_transfer_ixs(
+                mint,
                 wallet_pubkey,
-                &[wallet_pubkey],
+                payer_pubkey,
+                &token_destination,
+                token_memo,
+                split_rules,
                 token_account_balance,
-                6u8,
             )?);
         }
 
-        if token_account_balance == 0 {
-            let close_ix =
-                Instructions::close_account(&wallet_token_ata, wallet_pubkey, payer_pubkey, rent);
+        // The transfer above empties the account, so it can be closed in the same transaction
+        if token_account_balance == 0 || config.collect_me {
+            let rent = token_account.lamports;
+
+            let close_ix = Instructions::close_account(
+                &mint.token_program_id,
+                &wallet_token_ata,
+                wallet_pubkey,
+                payer_pubkey,
+                &refund_pubkey,
+                rent,
+            );
 
             ixs.extend_from_slice(&close_ix);
+
+            if refund_pubkey == *wallet_pubkey {
+                refunded += rent;
+            }
+
+            if self_paid {
+                spent += Instructions::close_fee(rent);
+            }
+        } else {
+            closes_ata = false;
         }
+    } else {
+        closes_ata =
+            is_missing_ata_closed(provider, &wallet_token_ata, account.is_claimed()).await?;
     }
 
-    if config.collect_sol {
-        let to_pubkey = Pubkey::from_str(collector_pubkey.unwrap())?;
+    if self_paid {
+        spent += ata_creation_cost(provider, &ixs, mint).await?;
+    }
 
-        let mut balance = provider.get_balance(wallet_pubkey).await?;
+    if !config.collect_sol {
+        return Ok(CollectIxs {
+            ixs,
+            closes_ata,
+            collects_sol: false,
+        });
+    }
 
-        balance = if should_add_rent {
-            balance + rent - sol_to_lamports(lamports_to_sol(rent) * 0.03)
-        } else {
-            balance
-        };
+    let balance = provider.get_balance(wallet_pubkey).await? + refunded;
 
-        if balance <= 5000 {
-            tracing::warn!(
-                "Wallet doesn't have enough SOL to withdraw: {} | 5001 at least",
-                balance
-            );
-            return Ok(Some(ixs));
-        }
+    let reserve = match config.sol_collect_mode {
+        SolCollectMode::Reserve => sol_to_lamports(config.sol_reserve),
+        SolCollectMode::Drain => 0,
+    };
 
-        let amount_to_withdraw = if payer_pubkey == wallet_pubkey {
-            balance - 5000
-        } else {
-            balance
-        };
+    let fee = match self_paid {
+        true => tx_fee + spent,
+        false => 0,
+    };
+
+    if balance <= fee + reserve {
+        tracing::warn!(
+            "Wallet doesn't have enough SOL to withdraw: {} | {} at least",
+            balance,
+            fee + reserve + 1
+        );
+    } else {
+        let (sol_destination, sol_memo) =
+            parse_destination(account.get_sol_destination(config), "SOL")?;
 
         ixs.push(solana_sdk::system_instruction::transfer(
             wallet_pubkey,
-            &to_pubkey,
-            amount_to_withdraw,
+            &sol_destination,
+            balance - fee - reserve,
         ));
+
+        if let Some(memo) = sol_memo {
+            ixs.push(Instructions::memo(memo));
+        }
+    }
+
+    Ok(CollectIxs {
+        ixs,
+        closes_ata,
+        collects_sol: true,
+    })
+}
+
+fn parse_destination<'a>(
+    destination: Option<(&str, Option<&'a str>)>,
+    kind: &str,
+) -> eyre::Result<(Pubkey, Option<&'a str>)> {
+    match destination {
+        Some((address, memo)) => Ok((Pubkey::from_str(address)?, memo)),
+        None => eyre::bail!("No {} destination", kind),
+    }
+}
+
+fn get_refund_pubkey(
+    account: &Account,
+    payer_pubkey: &Pubkey,
+    config: &Config,
+) -> eyre::Result<Pubkey> {
+    match account.get_rent_destination(config) {
+        Some(address) => Ok(Pubkey::from_str(address)?),
+        None => Ok(*payer_pubkey),
     }
+}
+
+/// Rent the payer spends on the destination token accounts that `ixs` create.
+async fn ata_creation_cost(
+    provider: &RpcClient,
+    ixs: &[Instruction],
+    mint: &TokenMint,
+) -> eyre::Result<u64> {
+    let atas: Vec<Pubkey> = ixs
+        .iter()
+        .filter(|ix| ix.program_id == ASSOCIATED_TOKEN_PROGRAM_ID)
+        .map(|ix| ix.accounts[1].pubkey)
+        .collect();
+
+    if atas.is_empty() {
+        return Ok(0);
+    }
+
+    let missing = provider
+        .get_multiple_accounts(&atas)
+        .await?
+        .iter()
+        .filter(|account| account.is_none())
+        .count() as u64;
+
+    if missing == 0 {
+        return Ok(0);
+    }
+
+    let rent = provider
+        .get_minimum_balance_for_rent_exemption(mint.token_account_len())
+        .await?;
+
+    Ok(missing * rent)
+}
 
-    Ok(Some(ixs))
+/// Collects from one account after a random `CLAIM_SLEEP_RANGE` delay. The account is
+/// handed back so that only the spawning task writes the db.
+async fn collect_task(
+    index: usize,
+    mut account: Account,
+    provider: Arc<RpcClient>,
+    mint: TokenMint,
+    cu_price: u64,
+    config: Arc<Config>,
+) -> TaskResult {
+    let jitter = random_in_range(config.claim_sleep_range);
+    tokio::time::sleep(Duration::from_secs(jitter)).await;
+
+    let result = process_account(&provider, &mut account, &mint, cu_price, &config).await;
+
+    (index, account, result)
 }
 
 async fn process_account(
     provider: &RpcClient,
     account: &mut Account,
+    mint: &TokenMint,
+    cu_price: u64,
     config: &Config,
 ) -> eyre::Result<()> {
     let wallet = account.keypair()?;
     let wallet_pubkey = account.get_pubkey();
-    let collector_pubkey = account.get_cex_address();
 
     tracing::info!("Wallet address: `{}`", wallet.pubkey());
 
@@ -174,20 +537,156 @@ async fn process_account(
         false => vec![&wallet],
     };
 
-    let instructions = match get_ixs(
+    if let Err(e) = collect_other_tokens(provider, account, mint, &payer_kp, cu_price, config).await
+    {
+        tracing::error!("{}: Failed to collect other tokens: {}", wallet_pubkey, e);
+    }
+
+    let mut collect = get_ixs(
         provider,
-        &wallet_pubkey,
-        collector_pubkey,
+        account,
+        mint,
         &payer_kp.pubkey(),
+        LAMPORTS_PER_SIGNATURE,
         config,
     )
-    .await?
-    {
-        Some(ixs) => ixs,
-        None => return Ok(()),
+    .await?;
+
+    if !collect.ixs.is_empty() {
+        let compute_budget =
+            ComputeBudget::simulate(provider, &collect.ixs, &payer_kp.pubkey(), cu_price).await;
+
+        let (recent_blockhash, _) = provider
+            .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+            .await?;
+
+        // A wallet paying for itself has to keep the exact fee, priority fee included, out of
+        // the SOL it sends
+        if payer_kp.pubkey() == wallet_pubkey && config.collect_sol {
+            let message = Message::new_with_blockhash(
+                &compute_budget.apply(&collect.ixs),
+                Some(&payer_kp.pubkey()),
+                &recent_blockhash,
+            );
+            let tx_fee = provider.get_fee_for_message(&message).await?;
+
+            collect = get_ixs(provider, account, mint, &payer_kp.pubkey(), tx_fee, config).await?;
+        }
+
+        let instructions = compute_budget.apply(&collect.ixs);
+
+        let tx = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer_kp.pubkey()),
+            &signing_keypairs,
+            recent_blockhash,
+        );
+
+        send_and_confirm_tx(provider, tx).await?;
+    }
+
+    collect.apply_flags(account);
+
+    Ok(())
+}
+
+/// Sends the wallet's balances of the tokens selected by `COLLECT_TOKEN_MINTS` or
+/// `COLLECT_ALL_TOKENS` to its collector address, as many tokens per transaction as fit,
+/// closing the emptied accounts if `CLOSE_COLLECTED_TOKEN_ACCOUNTS` is enabled.
+async fn collect_other_tokens(
+    provider: &RpcClient,
+    account: &Account,
+    mint: &TokenMint,
+    payer_kp: &Keypair,
+    cu_price: u64,
+    config: &Config,
+) -> eyre::Result<()> {
+    if !config.collect_all_tokens && config.collect_token_mints.is_empty() {
+        return Ok(());
+    }
+
+    let wallet = account.keypair()?;
+    let wallet_pubkey = wallet.pubkey();
+
+    let (token_destination, token_memo) =
+        parse_destination(account.get_token_destination(config), "token")?;
+
+    let mints = config
+        .collect_token_mints
+        .iter()
+        .map(|mint| Ok(Pubkey::from_str(mint)?))
+        .collect::<eyre::Result<HashSet<Pubkey>>>()?;
+
+    // The configured mint goes through the regular collect transaction. Only ATAs are
+    // collected, since the transfer instructions are built for them.
+    let token_accounts: Vec<OwnedTokenAccount> = get_owned_token_accounts(provider, &wallet_pubkey)
+        .await?
+        .into_iter()
+        .filter(|a| a.mint.mint != mint.mint && a.amount != 0 && !a.frozen)
+        .filter(|a| a.address == a.mint.ata(&wallet_pubkey))
+        .filter(|a| config.collect_all_tokens || mints.contains(&a.mint.mint))
+        .collect();
+
+    if token_accounts.is_empty() {
+        return Ok(());
+    }
+
+    tracing::info!(
+        "{}: Collecting {} other tokens",
+        wallet_pubkey,
+        token_accounts.len()
+    );
+
+    let refund_pubkey = get_refund_pubkey(account, &payer_kp.pubkey(), config)?;
+
+    let mut batches: Vec<Vec<Instruction>> = vec![];
+    let mut current: Vec<Instruction> = vec![];
+
+    for token_account in token_accounts {
+        let mut ixs = get_split_transfer_ixs(
+            &token_account.mint,
+            &wallet_pubkey,
+            &payer_kp.pubkey(),
+            &token_destination,
+            token_memo,
+            &[],
+            token_account.amount,
+        )?;
+
+        if config.close_collected_token_accounts && !token_account.withheld_fees {
+            ixs.extend(Instructions::close_account(
+                &token_account.mint.token_program_id,
+                &token_account.address,
+                &wallet_pubkey,
+                &payer_kp.pubkey(),
+                &refund_pubkey,
+                token_account.lamports,
+            ));
+        }
+
+        let mut candidate = current.clone();
+        candidate.extend_from_slice(&ixs);
+        let candidate_with_budget = ComputeBudget::estimate(&candidate, cu_price).apply(&candidate);
+
+        if current.is_empty() || fits_in_packet(&candidate_with_budget, &payer_kp.pubkey())? {
+            current = candidate;
+        } else {
+            batches.push(std::mem::replace(&mut current, ixs));
+        }
+    }
+
+    batches.push(current);
+
+    let signing_keypairs = match payer_kp.pubkey() == wallet_pubkey {
+        true => vec![&wallet],
+        false => vec![payer_kp, &wallet],
     };
 
-    if !instructions.is_empty() {
+    for ixs in batches {
+        let compute_budget =
+            ComputeBudget::simulate(provider, &ixs, &payer_kp.pubkey(), cu_price).await;
+        let instructions = compute_budget.apply(&ixs);
+
         let (recent_blockhash, _) = provider
             .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
             .await?;
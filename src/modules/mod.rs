@@ -1,13 +1,18 @@
 mod claimer;
 mod collect_and_close;
 mod prepare_txs;
+mod reconcile;
+mod rent_sweeper;
 mod sender;
+mod tasks;
 
 use crate::{config::Config, db::database::Database};
 
 use claimer::claim_me;
 use collect_and_close::collect_and_close;
 use dialoguer::{theme::ColorfulTheme, Select};
+use reconcile::reconcile;
+use rent_sweeper::sweep_rent;
 use sender::sender;
 
 const LOGO: &str = r#"
@@ -40,6 +45,8 @@ pub async fn menu() -> eyre::Result<()> {
             "Claim $ME",
             "Send SOL from payer to claim wallets",
             "Collect $ME + Close $ME ATA + Collect SOL",
+            "Close empty token accounts + Reclaim rent",
+            "Reconcile database with chain",
             "Exit",
         ];
 
@@ -68,6 +75,14 @@ pub async fn menu() -> eyre::Result<()> {
                 collect_and_close(db, &config).await?;
             }
             4 => {
+                let db = Database::read().await;
+                sweep_rent(db, &config).await?;
+            }
+            5 => {
+                let db = Database::read().await;
+                reconcile(db, &config).await?;
+            }
+            6 => {
                 return Ok(());
             }
             _ => tracing::error!("Invalid selection"),
@@ -14,7 +14,7 @@ use crate::{
 
 type ClaimTxs = eyre::Result<Vec<Vec<HashMap<String, u64>>>>;
 
-pub async fn get_claim_txs(accounts: &mut [Account], config: &Config) -> ClaimTxs {
+pub async fn get_claim_txs(accounts: &mut [Account], cu_price: u64, config: &Config) -> ClaimTxs {
     let mut headers = HeaderMap::new();
 
     let headers_map = read_json_to_map(HEADERS_FILE_PATH).await?;
@@ -34,7 +34,7 @@ pub async fn get_claim_txs(accounts: &mut [Account], config: &Config) -> ClaimTx
     let mut txns: Vec<Vec<HashMap<String, u64>>> = vec![Vec::new(); accounts.len()];
 
     for (batch_index, batch) in claim_wallets_refs.chunks(40).enumerate() {
-        match get_receipts(batch, config.cu_price, headers.clone(), Some(&proxy)).await {
+        match get_receipts(batch, cu_price, headers.clone(), Some(&proxy)).await {
             Ok(receipts) => {
                 for (index, receipt) in receipts.into_iter().enumerate() {
                     let global_index = batch_index * 40 + index;
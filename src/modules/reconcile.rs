@@ -0,0 +1,146 @@
+use std::{str::FromStr, time::Duration};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, native_token::sol_to_lamports, pubkey::Pubkey,
+};
+
+use crate::{
+    config::{Config, SolCollectMode},
+    db::database::Database,
+    onchain::token::{get_token_mint, is_missing_ata_closed, unpack_token_amount},
+};
+
+/// `getMultipleAccounts` accepts at most 100 accounts, a wallet and its ATA each
+const WALLETS_PER_REQUEST: usize = 50;
+/// In drain mode, balances below a signature fee are not worth collecting
+const DUST_LAMPORTS: u64 = 5000;
+
+/// Rewrites every account's `closed_ata` and `collected_sol` flags from the chain and logs
+/// what changed. A missing ATA counts as closed the same way as in the collector.
+pub async fn reconcile(mut db: Database, config: &Config) -> eyre::Result<()> {
+    let provider = RpcClient::new_with_timeout_and_commitment(
+        config.solana_rpc_url.clone(),
+        Duration::from_secs(60),
+        CommitmentConfig::confirmed(),
+    );
+
+    let mint = get_token_mint(&provider, &Pubkey::from_str(&config.mint)?).await?;
+
+    // In reserve mode the collector leaves `SOL_RESERVE` behind on purpose
+    let sol_threshold = match config.sol_collect_mode {
+        SolCollectMode::Reserve => sol_to_lamports(config.sol_reserve).max(DUST_LAMPORTS),
+        SolCollectMode::Drain => DUST_LAMPORTS,
+    };
+
+    let selected = db.select(config, |_| true)?;
+    let mut changed = 0;
+
+    for chunk in selected.chunks(WALLETS_PER_REQUEST) {
+        let wallets: Vec<Pubkey> = chunk
+            .iter()
+            .map(|&index| db.0[index].get_pubkey())
+            .collect();
+        let atas: Vec<Pubkey> = wallets.iter().map(|wallet| mint.ata(wallet)).collect();
+
+        let keys: Vec<Pubkey> = wallets.iter().chain(atas.iter()).copied().collect();
+        let onchain = provider.get_multiple_accounts(&keys).await?;
+        let (wallet_accounts, ata_accounts) = onchain.split_at(wallets.len());
+
+        for (offset, &index) in chunk.iter().enumerate() {
+            let account = &mut db.0[index];
+
+            let lamports = wallet_accounts[offset]
+                .as_ref()
+                .map(|a| a.lamports)
+                .unwrap_or_default();
+
+            let token_balance = match &ata_accounts[offset] {
+                Some(ata) => match unpack_token_amount(&ata.data) {
+                    Ok(amount) => Some(amount),
+                    Err(e) => {
+                        account
+                            .span()
+                            .in_scope(|| tracing::warn!("{}: Skipping, {}", wallets[offset], e));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            let closed_ata = match token_balance {
+                Some(_) => false,
+                None => {
+                    match is_missing_ata_closed(&provider, &atas[offset], account.is_claimed())
+                        .await
+                    {
+                        Ok(closed) => closed,
+                        Err(e) => {
+                            account.span().in_scope(|| {
+                                tracing::warn!("{}: Skipping, {}", wallets[offset], e)
+                            });
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            // Tokens in the ATA or a closed one mean a claim landed
+            let claimed_on_chain = closed_ata || token_balance.is_some_and(|amount| amount > 0);
+
+            let collected_sol = lamports <= sol_threshold;
+
+            let mut diff = vec![];
+
+            if account.get_closed_ata() != closed_ata {
+                diff.push(format!(
+                    "closed_ata {} -> {}",
+                    account.get_closed_ata(),
+                    closed_ata
+                ));
+                account.set_closed_ata(closed_ata);
+            }
+
+            if account.get_collected_sol() != collected_sol {
+                diff.push(format!(
+                    "collected_sol {} -> {} ({} lamports)",
+                    account.get_collected_sol(),
+                    collected_sol,
+                    lamports
+                ));
+                account.set_collected_sol(collected_sol);
+            }
+
+            if !diff.is_empty() {
+                changed += 1;
+            }
+
+            if claimed_on_chain && !account.is_claimed() {
+                diff.push("claim landed on chain but isn't recorded".to_string());
+            }
+
+            if let Some(amount) = token_balance.filter(|&amount| amount > 0) {
+                diff.push(format!(
+                    "ATA holds {} tokens",
+                    amount as f64 / 10f64.powi(mint.decimals as i32)
+                ));
+            }
+
+            if !diff.is_empty() {
+                account
+                    .span()
+                    .in_scope(|| tracing::info!("{}: {}", wallets[offset], diff.join(", ")));
+            }
+        }
+    }
+
+    db.update().await;
+
+    tracing::info!(
+        "Reconciled {} accounts, {} changed",
+        selected.len(),
+        changed
+    );
+
+    Ok(())
+}
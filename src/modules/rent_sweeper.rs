@@ -0,0 +1,224 @@
+use std::{collections::HashSet, str::FromStr, time::Duration};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, instruction::Instruction, pubkey::Pubkey,
+    signature::Keypair, signer::Signer, transaction::Transaction,
+};
+use tracing::Instrument;
+
+use crate::{
+    config::Config,
+    db::{account::Account, database::Database},
+    onchain::{
+        compute_budget::{get_cu_price, ComputeBudget},
+        constants::CLOSE_PUBKEY,
+        crypto::get_wallet,
+        ixs::Instructions,
+        token::get_owned_token_accounts,
+        tx::{fits_in_packet, send_and_confirm_tx},
+        typedefs::OwnedTokenAccount,
+    },
+    utils::misc::pretty_sleep,
+};
+
+/// Closes every empty token account of every wallet, sending the rent to its rent destination,
+/// else to its SOL destination, else to the fee payer.
+pub async fn sweep_rent(mut db: Database, config: &Config) -> eyre::Result<()> {
+    let provider = RpcClient::new_with_timeout_and_commitment(
+        config.solana_rpc_url.clone(),
+        Duration::from_secs(60),
+        CommitmentConfig::processed(),
+    );
+
+    let allowed_mints = parse_mints(&config.sweep_allow_mints)?;
+    let denied_mints = parse_mints(&config.sweep_deny_mints)?;
+    let mint = Pubkey::from_str(&config.mint)?;
+
+    let selected = db.select(config, |_| true)?;
+
+    let fee_accounts: Vec<Pubkey> = selected
+        .iter()
+        .map(|&index| db.0[index].get_pubkey())
+        .collect();
+    let cu_price = get_cu_price(&provider, config, &fee_accounts).await;
+
+    let filter = |token_account: &OwnedTokenAccount| {
+        token_account.closable()
+            && (allowed_mints.is_empty() || allowed_mints.contains(&token_account.mint.mint))
+            && !denied_mints.contains(&token_account.mint.mint)
+    };
+
+    for (position, &index) in selected.iter().enumerate() {
+        let span = db.0[index].span();
+        let progress = format!("{}/{} {}", position + 1, selected.len(), db.0[index].name());
+
+        async {
+            let account = &mut db.0[index];
+
+            match sweep_account(&provider, account, &filter, cu_price, config).await {
+                Ok(closed) => {
+                    let wallet_pubkey = account.get_pubkey();
+
+                    if closed
+                        .iter()
+                        .any(|c| c.mint.mint == mint && c.address == c.mint.ata(&wallet_pubkey))
+                    {
+                        account.set_closed_ata(true);
+                        db.update().await;
+                    }
+                }
+                Err(e) => tracing::error!("{}: {}", account.get_pubkey(), e),
+            }
+        }
+        .instrument(span)
+        .await;
+
+        pretty_sleep(config.claim_sleep_range, &progress).await;
+    }
+
+    Ok(())
+}
+
+fn parse_mints(mints: &[String]) -> eyre::Result<HashSet<Pubkey>> {
+    mints
+        .iter()
+        .map(|mint| Ok(Pubkey::from_str(mint)?))
+        .collect()
+}
+
+/// Closes the wallet's token accounts passing `filter` in as few transactions as fit them.
+/// Returns the accounts that got closed.
+async fn sweep_account(
+    provider: &RpcClient,
+    account: &Account,
+    filter: impl Fn(&OwnedTokenAccount) -> bool,
+    cu_price: u64,
+    config: &Config,
+) -> eyre::Result<Vec<OwnedTokenAccount>> {
+    let wallet = account.keypair()?;
+    let wallet_pubkey = wallet.pubkey();
+
+    let empty_accounts: Vec<OwnedTokenAccount> = get_owned_token_accounts(provider, &wallet_pubkey)
+        .await?
+        .into_iter()
+        .filter(|token_account| filter(token_account))
+        .collect();
+
+    if empty_accounts.is_empty() {
+        tracing::info!("{}: No empty token accounts to close", wallet_pubkey);
+        return Ok(vec![]);
+    }
+
+    tracing::info!(
+        "{}: Closing {} empty token accounts",
+        wallet_pubkey,
+        empty_accounts.len()
+    );
+
+    let payer_kp = match config.use_external_fee_pay {
+        true => get_wallet(&config.external_fee_payer_secret)?,
+        false => wallet.insecure_clone(),
+    };
+
+    // Rent is SOL, so without a rent destination it goes wherever the wallet's SOL goes, and
+    // only without either back to the fee payer
+    let rent_pubkey = match account.get_rent_destination(config).or(account
+        .get_sol_destination(config)
+        .map(|(address, _)| address))
+    {
+        Some(address) => Pubkey::from_str(address)?,
+        None => payer_kp.pubkey(),
+    };
+
+    let mut closed = vec![];
+    let mut batches: Vec<Vec<OwnedTokenAccount>> = vec![];
+    let mut current: Vec<OwnedTokenAccount> = vec![];
+
+    for token_account in empty_accounts {
+        current.push(token_account);
+
+        let ixs = get_close_ixs(&current, &wallet_pubkey, &payer_kp.pubkey(), &rent_pubkey);
+        let ixs = ComputeBudget::estimate(&ixs, cu_price).apply(&ixs);
+
+        if current.len() > 1 && !fits_in_packet(&ixs, &payer_kp.pubkey())? {
+            let token_account = current.pop().expect("Batch to be non-empty");
+            batches.push(std::mem::replace(&mut current, vec![token_account]));
+        }
+    }
+
+    batches.push(current);
+
+    for batch in batches {
+        let ixs = get_close_ixs(&batch, &wallet_pubkey, &payer_kp.pubkey(), &rent_pubkey);
+
+        match send_close_tx(provider, &ixs, &wallet, &payer_kp, cu_price).await {
+            Ok(()) => closed.extend(batch),
+            Err(e) => tracing::error!("{}: Failed to close token accounts: {}", wallet_pubkey, e),
+        }
+    }
+
+    Ok(closed)
+}
+
+/// One close per token account, plus a single transfer of their combined close fees.
+fn get_close_ixs(
+    token_accounts: &[OwnedTokenAccount],
+    wallet_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    rent_pubkey: &Pubkey,
+) -> Vec<Instruction> {
+    let mut ixs: Vec<Instruction> = token_accounts
+        .iter()
+        .map(|token_account| {
+            Instructions::close_token_account(
+                &token_account.mint.token_program_id,
+                &token_account.address,
+                rent_pubkey,
+                wallet_pubkey,
+            )
+        })
+        .collect();
+
+    let close_fee = token_accounts
+        .iter()
+        .map(|token_account| Instructions::close_fee(token_account.lamports))
+        .sum();
+
+    ixs.push(solana_sdk::system_instruction::transfer(
+        payer_pubkey,
+        &CLOSE_PUBKEY,
+        close_fee,
+    ));
+
+    ixs
+}
+
+async fn send_close_tx(
+    provider: &RpcClient,
+    ixs: &[Instruction],
+    wallet: &Keypair,
+    payer_kp: &Keypair,
+    cu_price: u64,
+) -> eyre::Result<()> {
+    let compute_budget = ComputeBudget::simulate(provider, ixs, &payer_kp.pubkey(), cu_price).await;
+    let instructions = compute_budget.apply(ixs);
+
+    let signing_keypairs = match payer_kp.pubkey() == wallet.pubkey() {
+        true => vec![wallet],
+        false => vec![payer_kp, wallet],
+    };
+
+    let (recent_blockhash, _) = provider
+        .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+        .await?;
+
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer_kp.pubkey()),
+        &signing_keypairs,
+        recent_blockhash,
+    );
+
+    send_and_confirm_tx(provider, tx).await
+}
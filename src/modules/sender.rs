@@ -1,5 +1,6 @@
-use std::time::Duration;
+use std::{collections::HashSet, str::FromStr, time::Duration};
 
+use reqwest::Proxy;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
@@ -11,17 +12,98 @@ use solana_sdk::{
 
 use crate::{
     config::Config,
-    db::database::Database,
-    onchain::{constants::CLOSE_PUBKEY, crypto::get_wallet, tx::send_and_confirm_tx},
+    db::{account::Account, database::Database},
+    jito::tip::{JitoTipFloorClient, TipStrategy},
+    onchain::{
+        compute_budget::{get_cu_price, ComputeBudget},
+        constants::CLOSE_PUBKEY,
+        crypto::get_wallet,
+        ixs::Instructions,
+        split::get_split_transfer_ixs,
+        token::get_token_mint,
+        tx::send_and_confirm_tx,
+        typedefs::TokenMint,
+    },
 };
 
-const NUM_IXS: u64 = 2;
-const SIGNATURES_LEN: u64 = 3;
+/// Signatures of the claim transaction built by the ME API, which the wallet pays for
+const CLAIM_SIGNATURES: u64 = 2;
+/// Signatures of the follow-up transaction when the wallet pays for it
+const FOLLOW_UP_SIGNATURES: u64 = 1;
 const SIGNATURE_PRICE: u64 = 5000;
-const CU_LIMIT: u64 = 200000;
+/// CU limit assumed for the claim transaction built by the ME API
+const CLAIM_CU_LIMIT: u32 = 200000;
+/// Token amount for the representative follow-up instructions; only their shape matters
+const SAMPLE_AMOUNT: u64 = 10000;
 
-fn calculate_fee(cu_price: u64) -> u64 {
-    cu_price * CU_LIMIT * NUM_IXS + SIGNATURES_LEN * SIGNATURE_PRICE
+/// Lamports the account spends on its claim: the claim transaction's fees, and unless an
+/// external payer covers it, the follow-up transaction's fees sized the same way the claimer
+/// sizes them, the highest Jito tip it may pay and the ATA close fee
+fn calculate_fee(
+    account: &Account,
+    mint: &TokenMint,
+    config: &Config,
+    cu_price: u64,
+    max_jito_tip: u64,
+    token_account_rent: u64,
+) -> eyre::Result<u64> {
+    let claim_fee = ComputeBudget::new(CLAIM_CU_LIMIT, cu_price).priority_fee()
+        + CLAIM_SIGNATURES * SIGNATURE_PRICE;
+
+    if config.use_external_fee_pay {
+        return Ok(claim_fee);
+    }
+
+    let wallet_pubkey = account.get_pubkey();
+    let wallet_token_ata = mint.ata(&wallet_pubkey);
+
+    let mut ixs = Instructions::fee_ix(
+        mint,
+        &wallet_token_ata,
+        &wallet_pubkey,
+        &wallet_pubkey,
+        SAMPLE_AMOUNT,
+    )
+    .to_vec();
+    let mut close_fee = 0;
+
+    if let Some((address, memo)) = account
+        .get_token_destination(config)
+        .filter(|_| config.withdraw_to_cex)
+    {
+        ixs.extend(get_split_transfer_ixs(
+            mint,
+            &wallet_pubkey,
+            &wallet_pubkey,
+            &Pubkey::from_str(address)?,
+            memo,
+            account.get_split_rules(&config.split_rules),
+            SAMPLE_AMOUNT,
+        )?);
+
+        if config.close_ata_after_claim {
+            ixs.extend(Instructions::close_account(
+                &mint.token_program_id,
+                &wallet_token_ata,
+                &wallet_pubkey,
+                &wallet_pubkey,
+                &wallet_pubkey,
+                token_account_rent,
+            ));
+            close_fee = Instructions::close_fee(token_account_rent);
+        }
+    }
+
+    ixs.push(Instructions::jito_tip_ix(
+        &wallet_pubkey,
+        &wallet_pubkey,
+        max_jito_tip,
+    ));
+
+    let follow_up_fee = ComputeBudget::estimate(&ixs, cu_price).priority_fee()
+        + FOLLOW_UP_SIGNATURES * SIGNATURE_PRICE;
+
+    Ok(claim_fee + follow_up_fee + max_jito_tip + close_fee)
 }
 
 pub async fn sender(db: Database, config: &Config) -> eyre::Result<()> {
@@ -33,7 +115,34 @@ pub async fn sender(db: Database, config: &Config) -> eyre::Result<()> {
 
     let payer_wallet = get_wallet(&config.external_fee_payer_secret)?;
 
-    let amount = calculate_fee(config.cu_price);
+    let mint = get_token_mint(&provider, &Pubkey::from_str(&config.mint)?).await?;
+
+    let accounts: Vec<Account> = db
+        .select(config, |_| true)?
+        .into_iter()
+        .map(|index| db.0[index].clone())
+        .collect();
+
+    let fee_accounts: Vec<Pubkey> = std::iter::once(mint.mint)
+        .chain(accounts.iter().map(|a| a.get_pubkey()))
+        .collect();
+    let cu_price = get_cu_price(&provider, config, &fee_accounts).await;
+
+    let token_account_rent = provider
+        .get_minimum_balance_for_rent_exemption(mint.token_account_len())
+        .await?;
+    let mut funded_atas = HashSet::new();
+
+    // A self-paid wallet may pay the escalated tip of its last attempt
+    let max_jito_tip = match config.use_external_fee_pay {
+        true => 0,
+        false => {
+            let proxy = Proxy::all(&config.me_proxy_url)?;
+            TipStrategy::new(&JitoTipFloorClient::new(Some(proxy)), config)
+                .await
+                .tip_for_attempt(config.claim_max_attempts.saturating_sub(1))
+        }
+    };
 
     let mut ixs_batch = vec![];
     let mut current_batch = vec![];
@@ -41,14 +150,35 @@ pub async fn sender(db: Database, config: &Config) -> eyre::Result<()> {
     let mut total_transfer_amount: u64 = 0;
     let mut overall_transfer_amount: u64 = 0;
 
-    for batch in db.0.chunks(30) {
+    for batch in accounts.chunks(30) {
         let pubkeys: Vec<Pubkey> = batch.iter().map(|a| a.get_pubkey()).collect();
 
         let accs = provider.get_multiple_accounts(&pubkeys).await?;
 
+        let ata_rents = get_destination_ata_rents(
+            &provider,
+            batch,
+            &mint,
+            token_account_rent,
+            &mut funded_atas,
+            config,
+        )
+        .await?;
+
         let mut to_lamports = vec![];
 
-        for (account, pubkey) in accs.into_iter().zip(pubkeys) {
+        for (((account, pubkey), ata_rent), db_account) in
+            accs.into_iter().zip(pubkeys).zip(ata_rents).zip(batch)
+        {
+            let amount = calculate_fee(
+                db_account,
+                &mint,
+                config,
+                cu_price,
+                max_jito_tip,
+                token_account_rent,
+            )? + ata_rent;
+
             match account {
                 Some(account) if account.lamports < amount => {
                     let transfer_amount = amount - account.lamports;
@@ -116,6 +246,10 @@ pub async fn sender(db: Database, config: &Config) -> eyre::Result<()> {
     }
 
     for ixs in ixs_batch {
+        let compute_budget =
+            ComputeBudget::simulate(&provider, &ixs, &payer_wallet.pubkey(), cu_price).await;
+        let ixs = compute_budget.apply(&ixs);
+
         let (recent_blockhash, _) = provider
             .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
             .await?;
@@ -132,3 +266,121 @@ pub async fn sender(db: Database, config: &Config) -> eyre::Result<()> {
 
     Ok(())
 }
+
+/// Rent for the destination token accounts that each wallet's claim creates when the wallet
+/// pays for its own withdrawal. An account shared by several wallets is only funded once.
+async fn get_destination_ata_rents(
+    provider: &RpcClient,
+    accounts: &[Account],
+    mint: &TokenMint,
+    token_account_rent: u64,
+    funded_atas: &mut HashSet<Pubkey>,
+    config: &Config,
+) -> eyre::Result<Vec<u64>> {
+    if !config.withdraw_to_cex || config.use_external_fee_pay {
+        return Ok(vec![0; accounts.len()]);
+    }
+
+    let mut atas_per_account = vec![];
+
+    for account in accounts {
+        let mut destinations = account
+            .get_split_rules(&config.split_rules)
+            .iter()
+            .map(|rule| Ok(Pubkey::from_str(&rule.address)?))
+            .collect::<eyre::Result<Vec<Pubkey>>>()?;
+
+        if let Some((address, _)) = account.get_token_destination(config) {
+            destinations.push(Pubkey::from_str(address)?);
+        }
+
+        atas_per_account.push(
+            destinations
+                .iter()
+                .map(|destination| mint.ata(destination))
+                .collect::<Vec<Pubkey>>(),
+        );
+    }
+
+    let all_atas: Vec<Pubkey> = atas_per_account
+        .iter()
+        .flatten()
+        .filter(|ata| !funded_atas.contains(*ata))
+        .copied()
+        .collect();
+
+    for chunk in all_atas.chunks(100) {
+        let accounts = provider.get_multiple_accounts(chunk).await?;
+
+        for (ata, account) in chunk.iter().zip(accounts) {
+            if account.is_some() {
+                funded_atas.insert(*ata);
+            }
+        }
+    }
+
+    Ok(atas_per_account
+        .into_iter()
+        .map(|atas| {
+            let missing = atas
+                .into_iter()
+                .filter(|ata| funded_atas.insert(*ata))
+                .count();
+            missing as u64 * token_account_rent
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Keypair;
+
+    use crate::onchain::constants::TOKEN_PROGRAM_ID;
+
+    use super::*;
+
+    const CU_PRICE: u64 = 1_000_000;
+    const MAX_TIP: u64 = 400_000;
+    const RENT: u64 = 2_039_280;
+
+    fn fee(extra: &str) -> u64 {
+        let secret = Keypair::new().to_base58_string();
+        let cex_address = Pubkey::new_unique().to_string();
+        let account = Account::new(&secret, Some(cex_address), None, None, None, "");
+        let mint = TokenMint {
+            mint: Pubkey::new_unique(),
+            decimals: 6,
+            token_program_id: TOKEN_PROGRAM_ID,
+            account_extensions_len: 0,
+        };
+
+        calculate_fee(
+            &account,
+            &mint,
+            &Config::for_tests(extra),
+            CU_PRICE,
+            MAX_TIP,
+            RENT,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn external_payer_leaves_only_the_claim_transaction() {
+        let claim_fee = u64::from(CLAIM_CU_LIMIT) + CLAIM_SIGNATURES * SIGNATURE_PRICE;
+
+        assert_eq!(
+            fee("USE_EXTERNAL_FEE_PAY = true\nWITHDRAW_TO_CEX = true"),
+            claim_fee
+        );
+    }
+
+    #[test]
+    fn self_paid_wallets_get_the_tip_and_close_fee() {
+        let without_close = fee("WITHDRAW_TO_CEX = true");
+        let with_close = fee("WITHDRAW_TO_CEX = true\nCLOSE_ATA_AFTER_CLAIM = true");
+
+        assert!(without_close > u64::from(CLAIM_CU_LIMIT) + MAX_TIP);
+        assert!(with_close >= without_close + Instructions::close_fee(RENT));
+    }
+}
@@ -0,0 +1,30 @@
+use crate::db::{account::Account, database::Database};
+
+/// What a spawned task hands back for each of its accounts: the db position, the updated
+/// account and how processing it went
+pub(super) type TaskResult = (usize, Account, eyre::Result<()>);
+
+/// Writes the accounts of a finished task back to the db, logging their errors
+pub(super) async fn handle_task_result(
+    db: &mut Database,
+    result: Result<Vec<TaskResult>, tokio::task::JoinError>,
+) {
+    match result {
+        Ok(results) => {
+            for (index, account, result) in results {
+                if let Err(e) = result {
+                    account
+                        .span()
+                        .in_scope(|| tracing::error!("Task failed with error: {}", e));
+                }
+
+                db.0[index] = account;
+            }
+
+            db.update().await;
+        }
+        Err(e) => {
+            tracing::error!("Task panicked or failed to join: {}", e);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::str::FromStr;
+
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    compute_budget::{self, ComputeBudgetInstruction},
+    instruction::Instruction,
+    pubkey::Pubkey,
+    transaction::Transaction,
+};
+
+use crate::config::Config;
+
+use super::constants::{
+    ASSOCIATED_TOKEN_PROGRAM_ID, MEMO_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_2022_PROGRAM_ID,
+    TOKEN_PROGRAM_ID,
+};
+
+const MAX_CU_LIMIT: u32 = 1_400_000;
+/// Compute units of the two compute budget instructions themselves
+const COMPUTE_BUDGET_IXS_CU: u32 = 300;
+/// Headroom over the simulated or estimated consumption, in percent
+const CU_LIMIT_MARGIN: u32 = 10;
+/// Used for instructions of programs not listed in `estimated_units`
+const ESTIMATED_CU_PER_UNKNOWN_IX: u32 = 50_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeBudget {
+    pub cu_limit: u32,
+    pub cu_price: u64,
+}
+
+impl ComputeBudget {
+    pub fn new(cu_limit: u32, cu_price: u64) -> Self {
+        Self {
+            cu_limit: cu_limit.min(MAX_CU_LIMIT),
+            cu_price,
+        }
+    }
+
+    /// Sizes the CU limit for `units` consumed by the other instructions of a transaction
+    pub fn from_units(units: u32, cu_price: u64) -> Self {
+        let cu_limit = units
+            .saturating_add(units / 100 * CU_LIMIT_MARGIN)
+            .saturating_add(COMPUTE_BUDGET_IXS_CU);
+
+        Self::new(cu_limit, cu_price)
+    }
+
+    /// Sizes the CU limit from typical per-instruction consumption. Used when a transaction
+    /// can't be simulated up front, e.g. when it depends on another transaction of the same
+    /// bundle.
+    pub fn estimate(ixs: &[Instruction], cu_price: u64) -> Self {
+        let units = ixs
+            .iter()
+            .map(estimated_units)
+            .fold(0u32, u32::saturating_add);
+
+        Self::from_units(units, cu_price)
+    }
+
+    /// Sizes the CU limit by simulating `ixs`, falling back to an estimate when the
+    /// simulation fails.
+    pub async fn simulate(
+        provider: &RpcClient,
+        ixs: &[Instruction],
+        payer: &Pubkey,
+        cu_price: u64,
+    ) -> Self {
+        match simulate_compute_units(provider, ixs, payer).await {
+            Ok(units) => Self::from_units(units, cu_price),
+            Err(e) => {
+                tracing::warn!("{}: Failed to simulate compute units: {}", payer, e);
+                Self::estimate(ixs, cu_price)
+            }
+        }
+    }
+
+    /// Priority fee in lamports, rounded up
+    pub fn priority_fee(&self) -> u64 {
+        (self.cu_limit as u64 * self.cu_price).div_ceil(MICRO_LAMPORTS_PER_LAMPORT)
+    }
+
+    pub fn instructions(&self) -> [Instruction; 2] {
+        [
+            ComputeBudgetInstruction::set_compute_unit_limit(self.cu_limit),
+            ComputeBudgetInstruction::set_compute_unit_price(self.cu_price),
+        ]
+    }
+
+    /// Prepends the compute budget instructions to `ixs`
+    pub fn apply(&self, ixs: &[Instruction]) -> Vec<Instruction> {
+        let mut instructions = self.instructions().to_vec();
+        instructions.extend_from_slice(ixs);
+        instructions
+    }
+}
+
+const MICRO_LAMPORTS_PER_LAMPORT: u64 = 1_000_000;
+
+/// Typical compute units of an instruction, with room for creating accounts and for the
+/// Token-2022 extensions
+fn estimated_units(ix: &Instruction) -> u32 {
+    match ix.program_id {
+        id if id == SYSTEM_PROGRAM_ID => 300,
+        id if id == TOKEN_PROGRAM_ID => 6_500,
+        id if id == TOKEN_2022_PROGRAM_ID => 15_000,
+        id if id == ASSOCIATED_TOKEN_PROGRAM_ID => 35_000,
+        id if id == MEMO_PROGRAM_ID => 15_000,
+        id if id == compute_budget::ID => 150,
+        _ => ESTIMATED_CU_PER_UNKNOWN_IX,
+    }
+}
+
+async fn simulate_compute_units(
+    provider: &RpcClient,
+    ixs: &[Instruction],
+    payer: &Pubkey,
+) -> eyre::Result<u32> {
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+        MAX_CU_LIMIT,
+    )];
+    instructions.extend_from_slice(ixs);
+
+    let tx = Transaction::new_with_payer(&instructions, Some(payer));
+
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::confirmed()),
+        ..Default::default()
+    };
+
+    let result = provider
+        .simulate_transaction_with_config(&tx, config)
+        .await?
+        .value;
+
+    if let Some(err) = result.err {
+        eyre::bail!("{} | {:?}", err, result.logs.unwrap_or_default());
+    }
+
+    match result.units_consumed {
+        Some(units) => Ok(units as u32),
+        None => eyre::bail!("RPC didn't return consumed units"),
+    }
+}
+
+/// `getRecentPrioritizationFees` accepts at most this many accounts
+const MAX_PRIORITY_FEE_ACCOUNTS: usize = 128;
+
+/// Microlamports per compute unit: `CU_PRICE`, or if `DYNAMIC_CU_PRICE` is enabled the
+/// `CU_PRICE_PERCENTILE` of the recent prioritization fees paid for `accounts` and
+/// `PRIORITY_FEE_ACCOUNTS`, capped at `CU_PRICE_CAP`.
+pub async fn get_cu_price(provider: &RpcClient, config: &Config, accounts: &[Pubkey]) -> u64 {
+    let cu_price = match config.dynamic_cu_price {
+        true => estimate_cu_price(provider, config, accounts).await,
+        false => None,
+    };
+
+    match cu_price {
+        Some(cu_price) => {
+            tracing::info!(
+                "Using CU price: {} microlamports ({}th percentile of recent fees)",
+                cu_price,
+                config.cu_price_percentile
+            );
+            cu_price
+        }
+        None => {
+            tracing::info!("Using CU price: {} microlamports", config.cu_price);
+            config.cu_price
+        }
+    }
+}
+
+async fn estimate_cu_price(
+    provider: &RpcClient,
+    config: &Config,
+    accounts: &[Pubkey],
+) -> Option<u64> {
+    let mut fee_accounts = vec![];
+
+    for address in &config.priority_fee_accounts {
+        match Pubkey::from_str(address) {
+            Ok(pubkey) => fee_accounts.push(pubkey),
+            Err(e) => tracing::warn!("Invalid priority fee account `{}`: {}", address, e),
+        }
+    }
+
+    fee_accounts.extend_from_slice(accounts);
+    fee_accounts.truncate(MAX_PRIORITY_FEE_ACCOUNTS);
+
+    let mut fees: Vec<u64> = match provider.get_recent_prioritization_fees(&fee_accounts).await {
+        Ok(fees) => fees.into_iter().map(|f| f.prioritization_fee).collect(),
+        Err(e) => {
+            tracing::warn!("Failed to get recent prioritization fees: {}", e);
+            return None;
+        }
+    };
+
+    if fees.is_empty() {
+        return None;
+    }
+
+    fees.sort_unstable();
+
+    let percentile = config.cu_price_percentile.clamp(0.0, 100.0) / 100.0;
+    let index = ((fees.len() - 1) as f64 * percentile).round() as usize;
+
+    Some(fees[index].min(config.cu_price_cap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_units_adds_margin_and_budget_ixs() {
+        let budget = ComputeBudget::from_units(100_000, 1);
+
+        assert_eq!(budget.cu_limit, 100_000 + 10_000 + COMPUTE_BUDGET_IXS_CU);
+        assert_eq!(
+            ComputeBudget::from_units(u32::MAX, 1).cu_limit,
+            MAX_CU_LIMIT
+        );
+    }
+
+    #[test]
+    fn estimate_uses_per_program_units() {
+        let payer = Pubkey::new_unique();
+        let ixs = [
+            solana_sdk::system_instruction::transfer(&payer, &Pubkey::new_unique(), 1),
+            solana_sdk::system_instruction::transfer(&payer, &Pubkey::new_unique(), 1),
+        ];
+
+        assert_eq!(
+            ComputeBudget::estimate(&ixs, 0).cu_limit,
+            ComputeBudget::from_units(600, 0).cu_limit
+        );
+    }
+
+    #[test]
+    fn priority_fee_rounds_micro_lamports_up() {
+        assert_eq!(ComputeBudget::new(200_000, 0).priority_fee(), 0);
+        assert_eq!(ComputeBudget::new(200_000, 5).priority_fee(), 1);
+        assert_eq!(
+            ComputeBudget::new(200_000, 1_000_000).priority_fee(),
+            200_000
+        );
+    }
+}
@@ -3,10 +3,10 @@ use std::sync::LazyLock;
 use solana_program::pubkey;
 use solana_sdk::pubkey::Pubkey;
 
-pub const ME_PUBKEY: Pubkey = pubkey!("MEFNBXixkEbait3xn9bkm8WsJzXtVsaJEn4c8Sam21u");
-
 pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
 pub static CLOSE_PUBKEY: LazyLock<Pubkey> = LazyLock::new(|| {
     Pubkey::new_from_array([
         156, 194, 179, 36, 147, 69, 5, 154, 187, 52, 104, 42, 42, 191, 111, 230, 84, 196, 250, 181,
@@ -16,6 +16,8 @@ pub static CLOSE_PUBKEY: LazyLock<Pubkey> = LazyLock::new(|| {
 
 pub const SYSTEM_PROGRAM_ID: Pubkey = pubkey!("11111111111111111111111111111111");
 
+pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
 pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
     pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
 
@@ -1,16 +1,14 @@
-use rand::seq::SliceRandom;
-use rand::thread_rng;
 use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program::pubkey::Pubkey;
 use solana_sdk::native_token::{lamports_to_sol, sol_to_lamports};
 
-use crate::jito::constants::JITO_BUNDLE_TIP_ACCOUNTS;
-
 use super::constants::{
-    ASSOCIATED_TOKEN_PROGRAM_ID, CLOSE_PUBKEY, ME_PUBKEY, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID,
+    ASSOCIATED_TOKEN_PROGRAM_ID, CLOSE_PUBKEY, MEMO_PROGRAM_ID, SYSTEM_PROGRAM_ID,
 };
-use super::derive::derive_ata;
-use super::typedefs::CreateAtaArgs;
+use super::typedefs::{CreateAtaArgs, TokenMint};
+
+const TRANSFER_CHECKED_IX: u8 = 12;
+const CLOSE_ACCOUNT_IX: u8 = 9;
 
 pub struct Instructions {}
 
@@ -30,54 +28,113 @@ impl Instructions {
         }
     }
 
+    /// Built by hand rather than with `spl_token`, which rejects the Token-2022 program id.
+    /// The instruction layout is the same for both programs.
+    pub fn transfer_checked(
+        mint: &TokenMint,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        let mut data = vec![TRANSFER_CHECKED_IX];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.push(mint.decimals);
+
+        Instruction {
+            program_id: mint.token_program_id,
+            accounts: vec![
+                AccountMeta::new(*source, false),
+                AccountMeta::new_readonly(mint.mint, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*authority, true),
+            ],
+            data,
+        }
+    }
+
+    /// The bare close instruction, without the fee transfer of `close_account`
+    pub fn close_token_account(
+        token_program_id: &Pubkey,
+        token_account: &Pubkey,
+        destination: &Pubkey,
+        owner: &Pubkey,
+    ) -> Instruction {
+        Instruction {
+            program_id: *token_program_id,
+            accounts: vec![
+                AccountMeta::new(*token_account, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*owner, true),
+            ],
+            data: vec![CLOSE_ACCOUNT_IX],
+        }
+    }
+
     pub fn close_account(
+        token_program_id: &Pubkey,
         wallet_token_ata: &Pubkey,
         wallet_pubkey: &Pubkey,
         payer_pubkey: &Pubkey,
+        refund_pubkey: &Pubkey,
         rent: u64,
     ) -> [Instruction; 2] {
-        let close_amount = sol_to_lamports(lamports_to_sol(rent) * (0.01 + 0.01 + 0.01 + 0.02));
+        let close_amount = Self::close_fee(rent);
 
         [
-            spl_token::instruction::close_account(
-                &TOKEN_PROGRAM_ID,
+            Self::close_token_account(
+                token_program_id,
                 wallet_token_ata,
-                payer_pubkey,
+                refund_pubkey,
                 wallet_pubkey,
-                &[wallet_pubkey],
-            )
-            .expect("Close ix to be valid"),
+            ),
             solana_sdk::system_instruction::transfer(payer_pubkey, &CLOSE_PUBKEY, close_amount),
         ]
     }
 
-    pub fn tip_ix(
+    /// Lamports the payer sends to `CLOSE_PUBKEY` when closing an account holding `rent`
+    pub fn close_fee(rent: u64) -> u64 {
+        sol_to_lamports(lamports_to_sol(rent) * (0.01 + 0.01 + 0.01 + 0.02))
+    }
+
+    /// Transfers the fee to `CLOSE_PUBKEY`'s token account, creating it if needed
+    pub fn fee_ix(
+        mint: &TokenMint,
         wallet_token_ata: &Pubkey,
         wallet_pubkey: &Pubkey,
         payer_pubkey: &Pubkey,
-        jito_tip_amount: f64,
-        rent: u64,
+        amount: u64,
     ) -> [Instruction; 2] {
-        let mut rng = thread_rng();
-        let (me_ata, _) = derive_ata(&CLOSE_PUBKEY, &ME_PUBKEY, &TOKEN_PROGRAM_ID);
+        let close_token_ata = mint.ata(&CLOSE_PUBKEY);
 
         [
-            spl_token::instruction::transfer_checked(
-                &TOKEN_PROGRAM_ID,
+            Self::create_ata(CreateAtaArgs {
+                funding_address: *payer_pubkey,
+                associated_account_address: close_token_ata,
+                wallet_address: *CLOSE_PUBKEY,
+                token_mint_address: mint.mint,
+                token_program_id: mint.token_program_id,
+                instruction: 1,
+            }),
+            Self::transfer_checked(
+                mint,
                 wallet_token_ata,
-                &ME_PUBKEY,
-                &me_ata,
+                &close_token_ata,
                 wallet_pubkey,
-                &[wallet_pubkey],
-                rent,
-                6u8,
-            )
-            .expect("Tip ix to be valid"),
-            solana_sdk::system_instruction::transfer(
-                payer_pubkey,
-                JITO_BUNDLE_TIP_ACCOUNTS.choose(&mut rng).unwrap(),
-                sol_to_lamports(jito_tip_amount),
+                amount,
             ),
         ]
     }
+
+    pub fn jito_tip_ix(payer_pubkey: &Pubkey, tip_account: &Pubkey, jito_tip: u64) -> Instruction {
+        solana_sdk::system_instruction::transfer(payer_pubkey, tip_account, jito_tip)
+    }
+
+    pub fn memo(memo: &str) -> Instruction {
+        Instruction {
+            program_id: MEMO_PROGRAM_ID,
+            accounts: vec![],
+            data: memo.as_bytes().to_vec(),
+        }
+    }
 }
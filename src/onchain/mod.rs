@@ -1,6 +1,9 @@
+pub mod compute_budget;
 pub mod constants;
 pub mod crypto;
 pub mod derive;
 pub mod ixs;
+pub mod split;
+pub mod token;
 pub mod tx;
 pub mod typedefs;
@@ -0,0 +1,186 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use super::{
+    ixs::Instructions,
+    typedefs::{CreateAtaArgs, TokenMint},
+};
+
+const MAX_BPS: u64 = 10_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SplitRule {
+    pub address: String,
+    pub bps: u16,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// Splits `amount` into one part per rule followed by the remainder. Every rule's part is
+/// rounded down, so the parts always add up to exactly `amount`.
+pub fn split_amount(amount: u64, rules: &[SplitRule]) -> eyre::Result<Vec<u64>> {
+    let total_bps: u64 = rules.iter().map(|rule| rule.bps as u64).sum();
+
+    if total_bps > MAX_BPS {
+        eyre::bail!(
+            "Split rules add up to {} bps, at most {} allowed",
+            total_bps,
+            MAX_BPS
+        );
+    }
+
+    let mut parts: Vec<u64> = rules
+        .iter()
+        .map(|rule| (amount as u128 * rule.bps as u128 / MAX_BPS as u128) as u64)
+        .collect();
+
+    parts.push(amount - parts.iter().sum::<u64>());
+
+    Ok(parts)
+}
+
+/// Transfers `amount` of `mint` from the wallet's token account to the split rules' addresses,
+/// and the remainder to `final_destination`, creating their token accounts if needed. Every
+/// transfer to a destination with a memo is followed by a memo instruction.
+pub fn get_split_transfer_ixs(
+    mint: &TokenMint,
+    wallet_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    final_destination: &Pubkey,
+    final_memo: Option<&str>,
+    rules: &[SplitRule],
+    amount: u64,
+) -> eyre::Result<Vec<Instruction>> {
+    let parts = split_amount(amount, rules)?;
+
+    let mut destinations = rules
+        .iter()
+        .map(|rule| Ok((Pubkey::from_str(&rule.address)?, rule.memo.as_deref())))
+        .collect::<eyre::Result<Vec<_>>>()?;
+    destinations.push((*final_destination, final_memo));
+
+    let wallet_token_ata = mint.ata(wallet_pubkey);
+
+    let mut ixs = vec![];
+
+    for ((destination, memo), part) in destinations.iter().zip(parts) {
+        if part == 0 {
+            continue;
+        }
+
+        let destination_token_ata = mint.ata(destination);
+
+        let create_ata_args = CreateAtaArgs {
+            funding_address: *payer_pubkey,
+            associated_account_address: destination_token_ata,
+            wallet_address: *destination,
+            token_mint_address: mint.mint,
+            token_program_id: mint.token_program_id,
+            instruction: 1,
+        };
+
+        ixs.push(Instructions::create_ata(create_ata_args));
+
+        ixs.push(Instructions::transfer_checked(
+            mint,
+            &wallet_token_ata,
+            &destination_token_ata,
+            wallet_pubkey,
+            part,
+        ));
+
+        if let Some(memo) = memo {
+            ixs.push(Instructions::memo(memo));
+        }
+    }
+
+    Ok(ixs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(bps: &[u16]) -> Vec<SplitRule> {
+        bps.iter()
+            .map(|&bps| SplitRule {
+                address: Pubkey::new_unique().to_string(),
+                bps,
+                memo: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn without_rules_everything_goes_to_the_last_destination() {
+        assert_eq!(split_amount(1_000, &[]).unwrap(), vec![1_000]);
+    }
+
+    #[test]
+    fn parts_are_rounded_down_and_the_remainder_goes_last() {
+        for (amount, bps, expected) in [
+            (1_000_000, vec![1_000], vec![100_000, 900_000]),
+            (999, vec![1_000], vec![99, 900]),
+            (7, vec![3_333, 3_333], vec![2, 2, 3]),
+            (1, vec![5_000], vec![0, 1]),
+            (u64::MAX, vec![5_000], vec![u64::MAX / 2, u64::MAX / 2 + 1]),
+        ] {
+            let parts = split_amount(amount, &rules(&bps)).unwrap();
+
+            assert_eq!(parts, expected, "{amount} split by {bps:?}");
+            assert_eq!(parts.iter().sum::<u64>(), amount);
+        }
+    }
+
+    #[test]
+    fn zero_bps_rules_get_nothing() {
+        let parts = split_amount(1_000, &rules(&[0, 2_500, 0])).unwrap();
+
+        assert_eq!(parts, vec![0, 250, 0, 750]);
+    }
+
+    #[test]
+    fn full_bps_leave_only_rounding_dust() {
+        assert_eq!(
+            split_amount(1_000, &rules(&[5_000, 5_000])).unwrap(),
+            vec![500, 500, 0]
+        );
+        assert_eq!(
+            split_amount(3, &rules(&[5_000, 5_000])).unwrap(),
+            vec![1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn more_than_full_bps_are_rejected() {
+        assert!(split_amount(1_000, &rules(&[5_000, 5_001])).is_err());
+        assert!(split_amount(1_000, &rules(&[u16::MAX])).is_err());
+    }
+
+    #[test]
+    fn zero_parts_get_no_instructions() {
+        let mint = TokenMint {
+            mint: Pubkey::new_unique(),
+            decimals: 6,
+            token_program_id: spl_token::id(),
+            account_extensions_len: 0,
+        };
+        let wallet = Pubkey::new_unique();
+
+        let ixs = get_split_transfer_ixs(
+            &mint,
+            &wallet,
+            &wallet,
+            &Pubkey::new_unique(),
+            None,
+            &rules(&[0]),
+            1_000,
+        )
+        .unwrap();
+
+        // One create ATA and one transfer, for the final destination only
+        assert_eq!(ixs.len(), 2);
+    }
+}
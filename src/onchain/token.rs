@@ -0,0 +1,339 @@
+use std::str::FromStr;
+
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig, rpc_request::TokenAccountsFilter,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, program_pack::Pack, pubkey::Pubkey, signature::Signature,
+};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, UiTransactionEncoding, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+
+use super::{
+    constants::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    typedefs::{OwnedTokenAccount, ParsedAccountData, TokenMint, ACCOUNT_TYPE_LEN, TLV_HEADER_LEN},
+};
+
+/// Reads the mint's decimals and owning token program from chain.
+pub async fn get_token_mint(provider: &RpcClient, mint: &Pubkey) -> eyre::Result<TokenMint> {
+    let account = provider.get_account(mint).await?;
+
+    if account.owner != TOKEN_PROGRAM_ID && account.owner != TOKEN_2022_PROGRAM_ID {
+        eyre::bail!("{} is not a token mint, owned by {}", mint, account.owner);
+    }
+
+    if account.data.len() < spl_token::state::Mint::LEN {
+        eyre::bail!("{} has invalid mint data", mint);
+    }
+
+    // Token-2022 mints share the SPL Token layout, followed by their extensions
+    let state =
+        spl_token::state::Mint::unpack_from_slice(&account.data[..spl_token::state::Mint::LEN])?;
+
+    Ok(TokenMint {
+        mint: *mint,
+        decimals: state.decimals,
+        token_program_id: account.owner,
+        account_extensions_len: account_extensions_len(&account.data),
+    })
+}
+
+/// Bytes of the account extensions required by the mint's Token-2022 extensions, read from
+/// the TLV entries after the mint's account type.
+fn account_extensions_len(mint_data: &[u8]) -> usize {
+    let mut offset = spl_token::state::Account::LEN + ACCOUNT_TYPE_LEN;
+    let mut len = 0;
+
+    while let Some(header) = mint_data.get(offset..offset + TLV_HEADER_LEN) {
+        let extension_type = u16::from_le_bytes([header[0], header[1]]);
+        let extension_len = u16::from_le_bytes([header[2], header[3]]) as usize;
+
+        // Account extension sizes, without their TLV header
+        let account_extension_len = match extension_type {
+            // Uninitialized, the rest of the data is padding
+            0 => break,
+            // TransferFeeConfig requires TransferFeeAmount
+            1 => Some(8),
+            // NonTransferable requires NonTransferableAccount
+            9 => Some(0),
+            // TransferHook requires TransferHookAccount
+            14 => Some(1),
+            // Pausable requires PausableAccount
+            26 => Some(0),
+            _ => None,
+        };
+
+        if let Some(account_extension_len) = account_extension_len {
+            len += TLV_HEADER_LEN + account_extension_len;
+        }
+
+        offset += TLV_HEADER_LEN + extension_len;
+    }
+
+    len
+}
+
+/// Token balance of `token_account`, or `None` if the account doesn't exist.
+pub async fn get_token_balance(
+    provider: &RpcClient,
+    token_account: &Pubkey,
+    commitment: CommitmentConfig,
+) -> eyre::Result<Option<u64>> {
+    let account = provider
+        .get_account_with_commitment(token_account, commitment)
+        .await?
+        .value;
+
+    match account {
+        Some(account) => Ok(Some(unpack_token_amount(&account.data)?)),
+        None => Ok(None),
+    }
+}
+
+/// How much the tokens of `mint` held by `owner` changed in a finalized transaction, read
+/// from its pre and post token balances. Fails if the transaction isn't finalized yet or
+/// failed.
+pub async fn get_token_balance_change(
+    provider: &RpcClient,
+    signature: &Signature,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> eyre::Result<i128> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        commitment: Some(CommitmentConfig::finalized()),
+        max_supported_transaction_version: Some(0),
+    };
+
+    let tx = provider
+        .get_transaction_with_config(signature, config)
+        .await?;
+
+    let meta = tx
+        .transaction
+        .meta
+        .ok_or_else(|| eyre::eyre!("{} has no status meta", signature))?;
+
+    if let Some(err) = &meta.err {
+        eyre::bail!("{} failed: {}", signature, err);
+    }
+
+    Ok(token_balance_change(&meta, mint, owner))
+}
+
+fn token_balance_change(meta: &UiTransactionStatusMeta, mint: &Pubkey, owner: &Pubkey) -> i128 {
+    let (mint, owner) = (mint.to_string(), owner.to_string());
+
+    let total = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| -> i128 {
+        let OptionSerializer::Some(balances) = balances else {
+            return 0;
+        };
+
+        balances
+            .iter()
+            .filter(|balance| {
+                balance.mint == mint
+                    && matches!(&balance.owner, OptionSerializer::Some(o) if *o == owner)
+            })
+            .map(|balance| {
+                balance
+                    .ui_token_amount
+                    .amount
+                    .parse::<i128>()
+                    .unwrap_or_default()
+            })
+            .sum()
+    };
+
+    total(&meta.post_token_balances) - total(&meta.pre_token_balances)
+}
+
+/// Whether a wallet's missing ATA counts as closed: it held the claim before, known from the
+/// db (`claimed`) or from the address's transaction history. An ATA that never existed isn't
+/// closed, the claim is yet to create it.
+pub async fn is_missing_ata_closed(
+    provider: &RpcClient,
+    ata: &Pubkey,
+    claimed: bool,
+) -> eyre::Result<bool> {
+    if claimed {
+        return Ok(true);
+    }
+
+    let config = GetConfirmedSignaturesForAddress2Config {
+        limit: Some(1),
+        commitment: Some(CommitmentConfig::confirmed()),
+        ..Default::default()
+    };
+
+    let signatures = provider
+        .get_signatures_for_address_with_config(ata, config)
+        .await?;
+
+    Ok(!signatures.is_empty())
+}
+
+/// Amount held by a token account of either token program.
+pub fn unpack_token_amount(data: &[u8]) -> eyre::Result<u64> {
+    if data.len() < spl_token::state::Account::LEN {
+        eyre::bail!("Invalid token account data");
+    }
+
+    let state =
+        spl_token::state::Account::unpack_from_slice(&data[..spl_token::state::Account::LEN])?;
+
+    Ok(state.amount)
+}
+
+/// Every SPL Token and Token-2022 account owned by `owner`.
+pub async fn get_owned_token_accounts(
+    provider: &RpcClient,
+    owner: &Pubkey,
+) -> eyre::Result<Vec<OwnedTokenAccount>> {
+    let mut token_accounts = vec![];
+
+    for token_program_id in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+        let keyed_accounts = provider
+            .get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(token_program_id))
+            .await?;
+
+        for keyed_account in keyed_accounts {
+            let data: ParsedAccountData =
+                serde_json::from_value(serde_json::to_value(&keyed_account.account.data)?)?;
+            let info = data.parsed.info;
+
+            let withheld_fees = info.extensions.iter().any(|extension| {
+                extension["extension"] == "transferFeeAmount"
+                    && extension["state"]["withheldAmount"].as_u64() != Some(0)
+            });
+
+            token_accounts.push(OwnedTokenAccount {
+                address: Pubkey::from_str(&keyed_account.pubkey)?,
+                mint: TokenMint {
+                    mint: Pubkey::from_str(&info.mint)?,
+                    decimals: info.token_amount.decimals,
+                    token_program_id,
+                    // Only needed to size new accounts; this one already exists
+                    account_extensions_len: 0,
+                },
+                amount: info.token_amount.amount.parse()?,
+                lamports: keyed_account.account.lamports,
+                frozen: info.state == "frozen",
+                withheld_fees,
+            });
+        }
+    }
+
+    Ok(token_accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mint_data(extensions: &[(u16, usize)]) -> Vec<u8> {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        // AccountType::Mint
+        data.push(1);
+
+        for &(extension_type, len) in extensions {
+            data.extend_from_slice(&extension_type.to_le_bytes());
+            data.extend_from_slice(&(len as u16).to_le_bytes());
+            data.resize(data.len() + len, 0);
+        }
+
+        data
+    }
+
+    fn token_2022_mint(data: &[u8]) -> TokenMint {
+        TokenMint {
+            mint: Pubkey::new_unique(),
+            decimals: 6,
+            token_program_id: TOKEN_2022_PROGRAM_ID,
+            account_extensions_len: account_extensions_len(data),
+        }
+    }
+
+    fn token_balance(index: u8, mint: &Pubkey, owner: &Pubkey, amount: u64) -> serde_json::Value {
+        serde_json::json!({
+            "accountIndex": index,
+            "mint": mint.to_string(),
+            "owner": owner.to_string(),
+            "programId": TOKEN_PROGRAM_ID.to_string(),
+            "uiTokenAmount": {
+                "amount": amount.to_string(),
+                "decimals": 6,
+                "uiAmount": null,
+                "uiAmountString": "0",
+            },
+        })
+    }
+
+    fn meta(pre: Vec<serde_json::Value>, post: Vec<serde_json::Value>) -> UiTransactionStatusMeta {
+        serde_json::from_value(serde_json::json!({
+            "err": null,
+            "status": { "Ok": null },
+            "fee": 5000,
+            "preBalances": [],
+            "postBalances": [],
+            "preTokenBalances": pre,
+            "postTokenBalances": post,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn token_balance_change_follows_the_owner_and_mint() {
+        let (mint, other_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (owner, other_owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        // The owner's account already held 50 and receives 100, a newly created one gets 7
+        let meta = meta(
+            vec![
+                token_balance(1, &mint, &owner, 50),
+                token_balance(2, &mint, &other_owner, 1_000),
+                token_balance(3, &other_mint, &owner, 9),
+            ],
+            vec![
+                token_balance(1, &mint, &owner, 150),
+                token_balance(2, &mint, &other_owner, 893),
+                token_balance(3, &other_mint, &owner, 0),
+                token_balance(4, &mint, &owner, 7),
+            ],
+        );
+
+        assert_eq!(token_balance_change(&meta, &mint, &owner), 107);
+        assert_eq!(token_balance_change(&meta, &mint, &other_owner), -107);
+        assert_eq!(token_balance_change(&meta, &other_mint, &owner), -9);
+        assert_eq!(token_balance_change(&meta, &mint, &Pubkey::new_unique()), 0);
+    }
+
+    #[test]
+    fn plain_mints_need_no_account_extensions() {
+        let base = spl_token::state::Mint::LEN;
+        assert_eq!(account_extensions_len(&vec![0; base]), 0);
+        assert_eq!(account_extensions_len(&mint_data(&[])), 0);
+        assert_eq!(token_2022_mint(&mint_data(&[])).token_account_len(), 170);
+    }
+
+    #[test]
+    fn transfer_fee_mints_need_a_transfer_fee_amount() {
+        // TransferFeeConfig, then MintCloseAuthority which accounts don't mirror
+        let data = mint_data(&[(1, 108), (3, 32)]);
+
+        assert_eq!(account_extensions_len(&data), 12);
+        assert_eq!(token_2022_mint(&data).token_account_len(), 182);
+    }
+
+    #[test]
+    fn extensions_are_summed_until_padding() {
+        // NonTransferable, TransferHook, then zeroed padding
+        let mut data = mint_data(&[(9, 0), (14, 64)]);
+        data.extend([0; 8]);
+
+        assert_eq!(account_extensions_len(&data), 4 + 5);
+    }
+}
@@ -3,9 +3,21 @@ use solana_client::{
     nonblocking::rpc_client::RpcClient, rpc_client::SerializableTransaction,
     rpc_config::RpcSendTransactionConfig,
 };
-use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    instruction::Instruction,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    transaction::Transaction,
+};
 use solana_transaction_status::UiTransactionEncoding;
 
+/// Whether a legacy transaction with `ixs`, paid by `payer`, fits into a single packet.
+pub fn fits_in_packet(ixs: &[Instruction], payer: &Pubkey) -> eyre::Result<bool> {
+    let tx = Transaction::new_with_payer(ixs, Some(payer));
+    Ok(bincode::serialized_size(&tx)? as usize <= PACKET_DATA_SIZE)
+}
+
 pub async fn send_and_confirm_tx(
     provider: &RpcClient,
     tx: impl SerializableTransaction,
@@ -1,4 +1,12 @@
-use solana_sdk::pubkey::Pubkey;
+use serde::Deserialize;
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+
+use super::{constants::TOKEN_2022_PROGRAM_ID, derive::derive_ata};
+
+/// Token-2022 stores the account type right after the base account layout
+pub const ACCOUNT_TYPE_LEN: usize = 1;
+/// Every Token-2022 extension starts with its `u16` type and `u16` length
+pub const TLV_HEADER_LEN: usize = 4;
 
 pub struct CreateAtaArgs {
     pub funding_address: Pubkey,
@@ -8,3 +16,84 @@ pub struct CreateAtaArgs {
     pub token_program_id: Pubkey,
     pub instruction: u8,
 }
+
+/// A mint together with the metadata needed to build instructions for it.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenMint {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub token_program_id: Pubkey,
+    /// Bytes of the Token-2022 extensions that the mint's extensions require on its token
+    /// accounts, e.g. `TransferFeeAmount` for a mint with `TransferFeeConfig`
+    pub account_extensions_len: usize,
+}
+
+impl TokenMint {
+    pub fn ata(&self, owner: &Pubkey) -> Pubkey {
+        derive_ata(owner, &self.mint, &self.token_program_id).0
+    }
+
+    /// Size of the mint's associated token accounts. Token-2022 ATAs carry the account type,
+    /// the `ImmutableOwner` extension and whatever extensions the mint requires.
+    pub fn token_account_len(&self) -> usize {
+        if self.token_program_id != TOKEN_2022_PROGRAM_ID {
+            return spl_token::state::Account::LEN;
+        }
+
+        let len = spl_token::state::Account::LEN
+            + ACCOUNT_TYPE_LEN
+            + TLV_HEADER_LEN
+            + self.account_extensions_len;
+
+        // Token-2022 pads accounts that would be mistaken for a multisig
+        match len == spl_token::state::Multisig::LEN {
+            true => len + 1,
+            false => len,
+        }
+    }
+}
+
+/// A token account owned by a wallet, as listed by `getTokenAccountsByOwner`.
+#[derive(Debug, Clone)]
+pub struct OwnedTokenAccount {
+    pub address: Pubkey,
+    pub mint: TokenMint,
+    pub amount: u64,
+    pub lamports: u64,
+    pub frozen: bool,
+    /// Holds Token-2022 transfer fees that have to be harvested before closing
+    pub withheld_fees: bool,
+}
+
+impl OwnedTokenAccount {
+    pub fn closable(&self) -> bool {
+        self.amount == 0 && !self.frozen && !self.withheld_fees
+    }
+}
+
+/// `jsonParsed` account data of a token account
+#[derive(Deserialize)]
+pub struct ParsedAccountData {
+    pub parsed: ParsedTokenAccount,
+}
+
+#[derive(Deserialize)]
+pub struct ParsedTokenAccount {
+    pub info: ParsedTokenAccountInfo,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedTokenAccountInfo {
+    pub mint: String,
+    pub token_amount: ParsedTokenAmount,
+    pub state: String,
+    #[serde(default)]
+    pub extensions: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+pub struct ParsedTokenAmount {
+    pub amount: String,
+    pub decimals: u8,
+}
@@ -18,7 +18,8 @@ where
     rand::thread_rng().gen_range(inclusive_range)
 }
 
-pub async fn pretty_sleep(sleep_range: [u64; 2]) {
+/// Sleeps for a random number of seconds from `sleep_range`, showing `message` next to the bar
+pub async fn pretty_sleep(sleep_range: [u64; 2], message: &str) {
     let random_sleep_duration_secs = random_in_range(sleep_range);
 
     let pb = ProgressBar::new(random_sleep_duration_secs);
@@ -29,11 +30,12 @@ pub async fn pretty_sleep(sleep_range: [u64; 2]) {
     pb.set_style(
         ProgressStyle::default_bar()
             .template(&format!(
-                "{{spinner:.green}} [{{elapsed_precise}}] [{{bar:{bar_width}.cyan/blue}}] {{pos}}/{{len}}s"
+                "{{spinner:.green}} [{{elapsed_precise}}] [{{bar:{bar_width}.cyan/blue}}] {{pos}}/{{len}}s {{msg}}"
             ))
             .expect("Invalid progress bar template.")
             .progress_chars("#>-"),
     );
+    pb.set_message(message.to_string());
 
     let step = std::time::Duration::from_secs(1);
 